@@ -0,0 +1,100 @@
+# Tray menu
+menu-refresh = Aktualisieren
+menu-pause = Überwachung für 1 Stunde pausieren
+menu-paused = Überwachung pausiert (noch { $minutes } Min.)
+menu-copy-status = Status kopieren
+menu-open-logs = Protokollordner öffnen
+menu-open-history = Verlauf öffnen
+menu-firmware = Firmware-Version anzeigen
+menu-autostart = Beim Anmelden starten
+menu-settings = Einstellungen...
+menu-exit = Beenden
+
+# Device submenu
+device-level = Akku: { $percent } %
+device-level-unknown = Akku: unbekannt
+device-state = Status: { $state }
+device-firmware = Firmware: { $version }
+device-firmware-unknown = Firmware: unbekannt
+device-wired = Verbindung: Kabel
+device-wireless = Verbindung: Kabellos
+device-updated = Aktualisiert: { $age }
+state-discharging = Entlädt
+state-charging = Lädt
+state-full = Vollständig geladen
+state-asleep = Schläft
+state-waking = Wird aufgeweckt
+state-unknown = Unbekannt
+
+# Tooltip and menu status line
+mouse-generic = Maus
+status-normal = { $name }: { $percent } %
+status-charging = { $name }: { $percent } % (lädt)
+status-full = { $name }: Vollständig geladen
+status-asleep = { $name }: Maus schläft
+status-waking = { $name }: Wird aufgeweckt...
+status-not-found = Maus: Gerät nicht gefunden
+status-unknown = { $name }: Status unbekannt
+
+# Last known level while the mouse can't report one
+status-stale = { $name }: { $percent } % · { $age } · { $state }
+stale-asleep = schläft
+stale-waking = wird aufgeweckt
+stale-unknown = Status unbekannt
+
+# Age of the last known level
+age-just-now = gerade eben
+age-minutes = vor { $count } Min.
+age-hours = vor { $count } Std.
+age-days =
+    { $count ->
+        [one] vor { $count } Tag
+       *[other] vor { $count } Tagen
+    }
+
+# Firmware notification
+firmware-version = Firmware-Version: { $version }
+firmware-unavailable = Die Firmware-Version konnte nicht gelesen werden. Ist die Maus verbunden?
+
+# Tray click actions
+popup-title = Mausakku
+notifications-on = Benachrichtigungen an
+notifications-off = Benachrichtigungen aus
+
+# Notification titles
+error-settings = Fehler in den Einstellungen
+error-icon-pack = Fehler im Icon-Paket
+error-open = { $path } konnte nicht geöffnet werden
+error-autostart = „Beim Start ausführen“ konnte nicht geändert werden
+
+history-missing = Es wurde noch kein Akkuverlauf aufgezeichnet.
+
+# Values of the template placeholders, e.g. {state} and {remaining}
+value-discharging = entlädt
+value-charging = lädt
+value-full = voll
+value-asleep = schläft
+value-waking = wird aufgeweckt
+value-not-found = nicht gefunden
+value-unknown = unbekannt
+value-wired = Kabel
+value-wireless = kabellos
+duration-minutes = { $minutes } Min.
+duration-hours = { $hours } Std. { $minutes } Min.
+duration-days =
+    { $days ->
+        [one] { $days } Tag { $hours } Std.
+       *[other] { $days } Tage { $hours } Std.
+    }
+
+# Placeholder descriptions shown by preview-template
+placeholder-name = Mausmodell, z. B. Model D 2 PRO Wireless
+placeholder-percent = Akkustand ohne %-Zeichen, oder -- wenn unbekannt
+placeholder-state = entlädt, lädt, voll, schläft, wird aufgeweckt, nicht gefunden oder unbekannt
+placeholder-firmware = Firmware-Version, oder unbekannt
+placeholder-age = Alter des angezeigten Stands, z. B. gerade eben oder vor 14 Min.
+placeholder-remaining = geschätzte Zeit bis leer, beim Laden bis voll, oder unbekannt
+placeholder-connection = Kabel oder kabellos
+
+# Settings window title
+settings-title = Einstellungen der Akkuanzeige
@@ -0,0 +1,93 @@
+# Tray menu
+menu-refresh = Refresh
+menu-pause = Pause Monitoring for 1 Hour
+menu-paused = Monitoring Paused ({ $minutes } min left)
+menu-copy-status = Copy Status
+menu-open-logs = Open Log Folder
+menu-open-history = Open History
+menu-firmware = Show Firmware Version
+menu-autostart = Run at Startup
+menu-settings = Settings...
+menu-exit = Exit
+
+# Device submenu
+device-level = Battery: { $percent }%
+device-level-unknown = Battery: unknown
+device-state = State: { $state }
+device-firmware = Firmware: { $version }
+device-firmware-unknown = Firmware: unknown
+device-wired = Connection: Wired
+device-wireless = Connection: Wireless
+device-updated = Updated: { $age }
+state-discharging = Discharging
+state-charging = Charging
+state-full = Fully charged
+state-asleep = Asleep
+state-waking = Waking up
+state-unknown = Unknown
+
+# Tooltip and menu status line
+mouse-generic = Mouse
+status-normal = { $name }: { $percent }%
+status-charging = { $name }: { $percent }% (Charging)
+status-full = { $name }: Fully Charged
+status-asleep = { $name }: Mouse is asleep
+status-waking = { $name }: Waking up...
+status-not-found = Mouse: Device not found
+status-unknown = { $name }: Unknown status
+
+# Last known level while the mouse can't report one, e.g.
+# "Model D 2 PRO Wireless: 62% · 14 min ago · asleep"
+status-stale = { $name }: { $percent }% · { $age } · { $state }
+stale-asleep = asleep
+stale-waking = waking up
+stale-unknown = unknown status
+
+# Age of the last known level
+age-just-now = just now
+age-minutes = { $count } min ago
+age-hours = { $count } h ago
+age-days = { $count } d ago
+
+# Firmware notification
+firmware-version = Firmware Version: { $version }
+firmware-unavailable = Unable to retrieve firmware version. Is the mouse connected?
+
+# Tray click actions
+popup-title = Mouse battery
+notifications-on = Notifications on
+notifications-off = Notifications off
+
+# Notification titles
+error-settings = Settings error
+error-icon-pack = Icon pack error
+error-open = Could not open { $path }
+error-autostart = Could not change Run at Startup
+
+history-missing = No battery history has been recorded yet.
+
+# Values of the template placeholders, e.g. {state} and {remaining}
+value-discharging = discharging
+value-charging = charging
+value-full = full
+value-asleep = asleep
+value-waking = waking up
+value-not-found = not found
+value-unknown = unknown
+value-wired = wired
+value-wireless = wireless
+duration-minutes = { $minutes } min
+duration-hours = { $hours } h { $minutes } min
+duration-days = { $days } d { $hours } h
+
+# Placeholder descriptions shown by preview-template
+placeholder-name = mouse model, e.g. Model D 2 PRO Wireless
+placeholder-percent = battery level without the % sign, or -- when unknown
+placeholder-state = discharging, charging, full, asleep, waking up, not found or unknown
+placeholder-firmware = firmware version, or unknown
+placeholder-age = age of the level shown, e.g. just now or 14 min ago
+placeholder-remaining = estimated time until empty, or until full while charging, or unknown
+placeholder-connection = wired or wireless
+
+# Settings window title
+settings-title = Battery Monitor Settings
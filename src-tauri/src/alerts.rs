@@ -0,0 +1,150 @@
+use crate::hooks;
+use crate::last_known::BatteryReading;
+use crate::mouse_battery::MouseModel;
+use crate::settings::Settings;
+use std::collections::HashMap;
+use std::process::Command;
+
+// Re-arm low/critical alerts once the level rises this far above the threshold
+const REARM_MARGIN: u8 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlertEvent {
+    Changed,
+    Low,
+    Critical,
+    ChargingStarted,
+    ChargingStopped,
+}
+
+impl AlertEvent {
+    pub fn name(&self) -> &'static str {
+        match self {
+            AlertEvent::Changed => "change",
+            AlertEvent::Low => "low",
+            AlertEvent::Critical => "critical",
+            AlertEvent::ChargingStarted => "charging_started",
+            AlertEvent::ChargingStopped => "charging_stopped",
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct DeviceAlertState {
+    percentage: Option<u8>,
+    state: &'static str,
+    charging: Option<bool>,
+    low_fired: bool,
+    critical_fired: bool,
+}
+
+// Turns consecutive readings into events such as threshold crossings
+#[derive(Debug, Default)]
+pub struct AlertTracker {
+    devices: HashMap<Option<MouseModel>, DeviceAlertState>,
+}
+
+impl AlertTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn observe(&mut self, reading: &BatteryReading, settings: &Settings) -> Vec<AlertEvent> {
+        let notifications = &settings.notifications;
+        let status = &reading.status;
+        let device = self.devices.entry(status.get_mouse_model()).or_default();
+        let mut events = Vec::new();
+
+        let percentage = status.percentage();
+        let state = status.state_name();
+        if device.percentage != percentage || device.state != state {
+            events.push(AlertEvent::Changed);
+        }
+
+        // Charging is only known while the mouse reports a level
+        let charging = percentage.map(|_| state != "discharging");
+        match (device.charging, charging) {
+            (Some(false), Some(true)) => events.push(AlertEvent::ChargingStarted),
+            (Some(true), Some(false)) => events.push(AlertEvent::ChargingStopped),
+            _ => {}
+        }
+
+        if let (Some(level), Some(charging)) = (percentage, charging) {
+            if charging || level > notifications.low_threshold.saturating_add(REARM_MARGIN) {
+                device.low_fired = false;
+            }
+            if charging
+                || level
+                    > notifications
+                        .critical_threshold
+                        .saturating_add(REARM_MARGIN)
+            {
+                device.critical_fired = false;
+            }
+
+            if !charging && level <= notifications.critical_threshold && !device.critical_fired {
+                device.critical_fired = true;
+                device.low_fired = true;
+                events.push(AlertEvent::Critical);
+            } else if !charging && level <= notifications.low_threshold && !device.low_fired {
+                device.low_fired = true;
+                events.push(AlertEvent::Low);
+            }
+        }
+
+        device.percentage = percentage;
+        device.state = state;
+        if charging.is_some() {
+            device.charging = charging;
+        }
+
+        events
+    }
+}
+
+// Shows notifications and runs hooks for an event
+pub fn dispatch(event: AlertEvent, reading: &BatteryReading, settings: &Settings) {
+    let notifications = &settings.notifications;
+    let hooks = &settings.hooks;
+
+    let (notify, hook) = match event {
+        AlertEvent::Changed => (false, &hooks.on_change),
+        AlertEvent::Low => (true, &hooks.on_low),
+        AlertEvent::Critical => (true, &hooks.on_critical),
+        AlertEvent::ChargingStarted => (notifications.charging, &hooks.on_charging_started),
+        AlertEvent::ChargingStopped => (notifications.charging, &hooks.on_charging_stopped),
+    };
+
+    if notify && notifications.enabled {
+        notify_user(&reading.status.display_name(), &reading.get_tooltip());
+    }
+
+    if let Some(command) = hook {
+        if let Err(e) = hooks::run_hook(command, reading, event.name()) {
+            eprintln!("{}", e);
+        }
+    }
+}
+
+// Shows a desktop notification using the platform's standard tool
+pub fn notify_user(title: &str, message: &str) {
+    #[cfg(windows)]
+    {
+        let _ = Command::new("msg")
+            .args(["*", &format!("{}\n\n{}", title, message)])
+            .spawn();
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        let _ = Command::new("notify-send")
+            .args(["--app-name=Glorious Battery", title, message])
+            .spawn();
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        let script = format!("display notification {:?} with title {:?}", message, title);
+        let _ = Command::new("osascript").args(["-e", &script]).spawn();
+    }
+}
@@ -0,0 +1,220 @@
+use tauri::{AppHandle, Runtime};
+
+#[cfg(target_os = "linux")]
+use std::ffi::OsString;
+#[cfg(target_os = "linux")]
+use std::fs;
+#[cfg(target_os = "linux")]
+use std::path::{Path, PathBuf};
+#[cfg(not(target_os = "linux"))]
+use tauri_plugin_autostart::ManagerExt;
+
+// Added to the command line when the system starts the app at login
+pub const LAUNCH_ARG: &str = "--autostart";
+
+// Name the autostart plugin gave the entry, so existing ones keep working
+#[cfg(target_os = "linux")]
+const ENTRY_NAME: &str = "Model D2 Pro Battery Monitor";
+
+pub fn is_enabled<R: Runtime>(app: &AppHandle<R>) -> Result<bool, String> {
+    #[cfg(target_os = "linux")]
+    {
+        let _ = app;
+        Ok(is_enabled_at(&entry_path()?))
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        app.autolaunch().is_enabled().map_err(|e| e.to_string())
+    }
+}
+
+pub fn set_enabled<R: Runtime>(app: &AppHandle<R>, enabled: bool) -> Result<(), String> {
+    #[cfg(target_os = "linux")]
+    {
+        let _ = app;
+        let path = entry_path()?;
+        if enabled {
+            enable_at(&path, &launch_path()?)
+        } else {
+            disable_at(&path)
+        }
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let autolaunch = app.autolaunch();
+        let result = if enabled {
+            autolaunch.enable()
+        } else {
+            autolaunch.disable()
+        };
+        result.map_err(|e| e.to_string())
+    }
+}
+
+// XDG autostart entry, in $XDG_CONFIG_HOME/autostart or ~/.config/autostart.
+// The autostart plugin ignores XDG_CONFIG_HOME and doesn't quote the
+// executable, so the entry is written here instead.
+#[cfg(target_os = "linux")]
+pub fn entry_path() -> Result<PathBuf, String> {
+    entry_path_for(std::env::var_os("XDG_CONFIG_HOME"), dirs::home_dir())
+        .ok_or_else(|| "No config directory available".to_string())
+}
+
+// Entry path for the given XDG_CONFIG_HOME and home directory; a relative
+// XDG_CONFIG_HOME is ignored, as the base directory spec says
+#[cfg(target_os = "linux")]
+fn entry_path_for(xdg_config_home: Option<OsString>, home: Option<PathBuf>) -> Option<PathBuf> {
+    let config = match xdg_config_home.map(PathBuf::from) {
+        Some(dir) if dir.is_absolute() => dir,
+        _ => home?.join(".config"),
+    };
+    Some(
+        config
+            .join("autostart")
+            .join(format!("{}.desktop", ENTRY_NAME)),
+    )
+}
+
+// Executable to start, the AppImage rather than the binary unpacked from
+// it for this run
+#[cfg(target_os = "linux")]
+pub fn launch_path() -> Result<PathBuf, String> {
+    match std::env::var_os("APPIMAGE") {
+        Some(appimage) => Ok(PathBuf::from(appimage)),
+        None => std::env::current_exe().map_err(|e| e.to_string()),
+    }
+}
+
+// Enabled when the entry exists and isn't switched off by the desktop's
+// own autostart settings
+#[cfg(target_os = "linux")]
+pub fn is_enabled_at(path: &Path) -> bool {
+    let Ok(content) = fs::read_to_string(path) else {
+        return false;
+    };
+    !content.lines().any(|line| {
+        matches!(
+            line.trim().replace(' ', "").as_str(),
+            "Hidden=true" | "X-GNOME-Autostart-enabled=false"
+        )
+    })
+}
+
+#[cfg(target_os = "linux")]
+pub fn enable_at(path: &Path, exe: &Path) -> Result<(), String> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    }
+    fs::write(path, desktop_entry(exe))
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+#[cfg(target_os = "linux")]
+pub fn disable_at(path: &Path) -> Result<(), String> {
+    match fs::remove_file(path) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(format!("Failed to remove {}: {}", path.display(), e)),
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn desktop_entry(exe: &Path) -> String {
+    format!(
+        "[Desktop Entry]\n\
+         Type=Application\n\
+         Version=1.0\n\
+         Name={name}\n\
+         Comment=Shows the battery level of Glorious mice in the tray\n\
+         Exec={exec} {arg}\n\
+         Terminal=false\n\
+         StartupNotify=false\n\
+         X-GNOME-Autostart-enabled=true\n",
+        name = ENTRY_NAME,
+        exec = quote_exec(&exe.to_string_lossy()),
+        arg = LAUNCH_ARG,
+    )
+}
+
+// Quotes an Exec argument as the desktop entry spec asks: in double quotes
+// with ", `, $ and \ escaped, and % doubled
+#[cfg(target_os = "linux")]
+fn quote_exec(arg: &str) -> String {
+    let arg = arg.replace('%', "%%");
+    let reserved = |c: char| c.is_whitespace() || "\"'\\><~|&;$*?#()`".contains(c);
+    if !arg.contains(reserved) {
+        return arg;
+    }
+
+    let mut quoted = String::from("\"");
+    for c in arg.chars() {
+        if matches!(c, '"' | '`' | '$' | '\\') {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    // The file format unescapes backslashes once more before Exec is split
+    quoted.replace('\\', "\\\\")
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entry_in_xdg_config_home() {
+        let home = Some(PathBuf::from("/home/me"));
+        let entry = format!("autostart/{}.desktop", ENTRY_NAME);
+        assert_eq!(
+            entry_path_for(Some("/tmp/config".into()), home.clone()),
+            Some(Path::new("/tmp/config").join(&entry))
+        );
+        assert_eq!(
+            entry_path_for(None, home.clone()),
+            Some(Path::new("/home/me/.config").join(&entry))
+        );
+        assert_eq!(
+            entry_path_for(Some("config".into()), home),
+            Some(Path::new("/home/me/.config").join(&entry))
+        );
+        assert_eq!(entry_path_for(None, None), None);
+    }
+
+    #[test]
+    fn enable_and_disable_entry() {
+        let config = std::env::temp_dir().join(format!("autostart-test-{}", std::process::id()));
+        let path = config.join("autostart").join("entry.desktop");
+
+        // Missing entry: disabled, and disabling it again is fine
+        assert!(!is_enabled_at(&path));
+        assert_eq!(disable_at(&path), Ok(()));
+
+        enable_at(&path, Path::new("/opt/My Apps/battery$1")).unwrap();
+        let content = fs::read_to_string(&path).unwrap();
+        assert!(
+            content
+                .lines()
+                .any(|line| line == r#"Exec="/opt/My Apps/battery\\$1" --autostart"#),
+            "{}",
+            content
+        );
+        assert!(is_enabled_at(&path));
+
+        for switched_off in ["Hidden=true", "X-GNOME-Autostart-enabled = false"] {
+            fs::write(&path, format!("{}{}\n", content, switched_off)).unwrap();
+            assert!(!is_enabled_at(&path), "{}", switched_off);
+        }
+
+        disable_at(&path).unwrap();
+        assert!(!path.exists());
+        fs::remove_dir_all(&config).unwrap();
+    }
+
+    #[test]
+    fn plain_paths_are_not_quoted() {
+        assert_eq!(quote_exec("/usr/bin/battery"), "/usr/bin/battery");
+        assert_eq!(quote_exec("/opt/100%/battery"), "/opt/100%%/battery");
+    }
+}
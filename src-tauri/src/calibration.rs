@@ -0,0 +1,255 @@
+use crate::history::LogSample;
+
+// Maps a reported battery value to a corrected percentage. Tables are
+// (reported, percent) points sorted by reported value; values in between
+// are interpolated linearly. An empty table leaves the value unchanged.
+pub fn apply(table: &[(u8, u8)], reported: u8) -> u8 {
+    let (first, last) = match (table.first(), table.last()) {
+        (Some(first), Some(last)) => (*first, *last),
+        _ => return reported.min(100),
+    };
+
+    if reported <= first.0 {
+        return first.1;
+    }
+    if reported >= last.0 {
+        return last.1;
+    }
+
+    for pair in table.windows(2) {
+        let (lo, hi) = (pair[0], pair[1]);
+        if reported >= lo.0 && reported <= hi.0 {
+            let span = (hi.0 - lo.0) as f32;
+            let t = (reported - lo.0) as f32 / span;
+            let value = lo.1 as f32 + t * (hi.1 as f32 - lo.1 as f32);
+            return value.round().clamp(0.0, 100.0) as u8;
+        }
+    }
+
+    reported.min(100)
+}
+
+// Checks that a table is usable by `apply`
+pub fn validate(table: &[(u8, u8)]) -> Result<(), String> {
+    for pair in table.windows(2) {
+        if pair[1].0 <= pair[0].0 {
+            return Err(format!(
+                "calibration points must have increasing reported values ({} follows {})",
+                pair[1].0, pair[0].0
+            ));
+        }
+        if pair[1].1 < pair[0].1 {
+            return Err(format!(
+                "calibration percentages must not decrease ({} follows {})",
+                pair[1].1, pair[0].1
+            ));
+        }
+    }
+    if let Some((_, percent)) = table.iter().find(|(_, p)| *p > 100) {
+        return Err(format!("calibration percentage {} is above 100", percent));
+    }
+    Ok(())
+}
+
+// A pause between readings of more than GAP_FACTOR times the log's usual
+// spacing ends a discharge run: the app wasn't running, so the drain in
+// between is unknown. The spacing follows whatever polling.interval_secs
+// was; MIN_GAP_SECS keeps a slow read at short intervals from ending a run.
+const GAP_FACTOR: u64 = 3;
+const MIN_GAP_SECS: u64 = 10 * 60;
+
+fn max_gap(samples: &[LogSample]) -> u64 {
+    let mut spacings: Vec<u64> = samples
+        .windows(2)
+        .map(|pair| pair[1].timestamp.saturating_sub(pair[0].timestamp))
+        .collect();
+    spacings.sort_unstable();
+    let usual = spacings.get(spacings.len() / 2).copied().unwrap_or(0);
+    (usual * GAP_FACTOR).max(MIN_GAP_SECS)
+}
+
+// Builds a calibration table from a recorded full discharge of one mouse.
+// Assuming a constant drain, the true level at any moment is the fraction
+// of the run still remaining, so each reported value is mapped to the
+// average time at which it was seen.
+pub fn table_from_discharge(samples: &[LogSample], points: usize) -> Result<Vec<(u8, u8)>, String> {
+    let mut product_ids: Vec<u16> = samples.iter().map(|s| s.product_id).collect();
+    product_ids.sort_unstable();
+    product_ids.dedup();
+    if product_ids.len() > 1 {
+        let ids: Vec<String> = product_ids.iter().map(|id| format!("{:04x}", id)).collect();
+        return Err(format!(
+            "log has readings from several mice ({}), pick one with --model",
+            ids.join(", ")
+        ));
+    }
+
+    // Use the longest stretch of readings taken while not charging, without
+    // gaps between sessions
+    let max_gap = max_gap(samples);
+    let mut best: &[LogSample] = &[];
+    let mut start = 0;
+    for i in 0..=samples.len() {
+        let ends_run = i == samples.len()
+            || samples[i].charging
+            || (i > start
+                && samples[i]
+                    .timestamp
+                    .saturating_sub(samples[i - 1].timestamp)
+                    > max_gap);
+        if ends_run {
+            let run = &samples[start..i];
+            if run.len() > best.len() {
+                best = run;
+            }
+            start = if i < samples.len() && !samples[i].charging {
+                i
+            } else {
+                i + 1
+            };
+        }
+    }
+
+    if best.len() < 2 {
+        return Err("log does not contain a discharge run".to_string());
+    }
+
+    let t_start = best.first().unwrap().timestamp;
+    let t_end = best.last().unwrap().timestamp;
+    if t_end <= t_start {
+        return Err("discharge run has no duration".to_string());
+    }
+    let duration = (t_end - t_start) as f64;
+
+    // Average true level for every reported value
+    let mut sums = [(0f64, 0u32); 256];
+    for sample in best {
+        let remaining = (t_end - sample.timestamp) as f64 / duration * 100.0;
+        let entry = &mut sums[sample.raw as usize];
+        entry.0 += remaining;
+        entry.1 += 1;
+    }
+
+    let mut measured: Vec<(u8, u8)> = sums
+        .iter()
+        .enumerate()
+        .filter(|(_, (_, count))| *count > 0)
+        .map(|(raw, (sum, count))| (raw as u8, (sum / *count as f64).round() as u8))
+        .collect();
+
+    // Enforce a non-decreasing curve
+    let mut floor = 0;
+    for point in measured.iter_mut() {
+        point.1 = point.1.max(floor);
+        floor = point.1;
+    }
+
+    if measured.len() < 2 {
+        return Err("discharge run reports fewer than two distinct values".to_string());
+    }
+
+    // Reduce to the requested number of points, always keeping both ends
+    let points = points.clamp(2, measured.len());
+    let last = measured.len() - 1;
+    let mut table: Vec<(u8, u8)> = (0..points)
+        .map(|i| measured[i * last / (points - 1)])
+        .collect();
+    table.dedup_by_key(|p| p.0);
+
+    validate(&table)?;
+    Ok(table)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn discharge(start: u64, raws: &[u8]) -> Vec<LogSample> {
+        discharge_every(60, start, raws)
+    }
+
+    fn discharge_every(spacing: u64, start: u64, raws: &[u8]) -> Vec<LogSample> {
+        raws.iter()
+            .enumerate()
+            .map(|(i, &raw)| LogSample {
+                timestamp: start + i as u64 * spacing,
+                product_id: 0x2034,
+                raw,
+                charging: false,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn apply_interpolates_and_clamps() {
+        let table = [(10, 0), (50, 20), (100, 100)];
+        assert_eq!(apply(&table, 0), 0);
+        assert_eq!(apply(&table, 30), 10);
+        assert_eq!(apply(&table, 50), 20);
+        assert_eq!(apply(&table, 75), 60);
+        assert_eq!(apply(&table, 120), 100);
+        assert_eq!(apply(&[], 42), 42);
+        assert_eq!(apply(&[], 120), 100);
+    }
+
+    #[test]
+    fn validate_rejects_unusable_tables() {
+        assert!(validate(&[]).is_ok());
+        assert!(validate(&[(0, 0), (50, 20), (100, 100)]).is_ok());
+        assert!(validate(&[(50, 20), (50, 30)]).is_err());
+        assert!(validate(&[(0, 50), (100, 40)]).is_err());
+        assert!(validate(&[(0, 0), (100, 101)]).is_err());
+    }
+
+    #[test]
+    fn table_maps_reported_values_to_remaining_time() {
+        let samples = discharge(1000, &[100, 90, 80, 30, 20, 10, 0]);
+        let table = table_from_discharge(&samples, 3).unwrap();
+        assert_eq!(table, vec![(0, 0), (30, 50), (100, 100)]);
+    }
+
+    #[test]
+    fn table_uses_longest_run_between_charges_and_gaps() {
+        // A short run, a charge, a long run, then readings from a later
+        // session after the app was closed
+        let mut samples = discharge(0, &[100, 50]);
+        samples.push(LogSample {
+            timestamp: 200,
+            product_id: 0x2034,
+            raw: 60,
+            charging: true,
+        });
+        samples.extend(discharge(300, &[100, 80, 60, 40, 20]));
+        samples.extend(discharge(300 + 4 * 60 + MIN_GAP_SECS + 1, &[10, 5, 0]));
+
+        let table = table_from_discharge(&samples, 11).unwrap();
+        assert_eq!(
+            table,
+            vec![(20, 0), (40, 25), (60, 50), (80, 75), (100, 100)]
+        );
+    }
+
+    #[test]
+    fn gaps_follow_the_polling_interval() {
+        // Polled every 30 minutes, then again after the app was closed for
+        // a few hours
+        let mut samples = discharge_every(30 * 60, 0, &[100, 75, 50, 25, 0]);
+        samples.extend(discharge_every(30 * 60, 4 * 30 * 60 + 4 * 3600, &[90, 80]));
+
+        let table = table_from_discharge(&samples, 11).unwrap();
+        assert_eq!(
+            table,
+            vec![(0, 0), (25, 25), (50, 50), (75, 75), (100, 100)]
+        );
+    }
+
+    #[test]
+    fn table_needs_a_single_mouse() {
+        let mut samples = discharge(0, &[100, 50, 0]);
+        samples[1].product_id = 0x2011;
+        let error = table_from_discharge(&samples, 11).unwrap_err();
+        assert!(error.contains("2011, 2034"), "{}", error);
+
+        assert!(table_from_discharge(&discharge(0, &[100]), 11).is_err());
+    }
+}
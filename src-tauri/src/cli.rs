@@ -0,0 +1,743 @@
+use crate::icon::IconStyle;
+use crate::icon_pack::{self, IconPack};
+use crate::last_known::{BatteryReading, LastKnown};
+use crate::monitor::Monitor;
+use crate::mouse_battery::{BatteryStatus, MouseBattery, MouseModel};
+use crate::status_bar::{self, BarFormat, BarStatus};
+use crate::theme::Theme;
+#[cfg(target_os = "linux")]
+use crate::{autostart, daemon};
+use crate::{calibration, history, i18n, icon, log_error, settings, template};
+use clap::{Args, CommandFactory, Parser, Subcommand};
+use image::RgbaImage;
+use serde::de::DeserializeOwned;
+use std::ffi::OsString;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+#[derive(Parser)]
+#[command(
+    name = "model-d2-pro-battery",
+    version,
+    about = "Glorious mouse battery monitor"
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// Started at login; passed by the autostart entry
+    #[arg(long, alias = "minimized", hide = true)]
+    autostart: bool,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Generate a calibration table from a recorded full-discharge log
+    Calibrate {
+        /// History log to read (defaults to the app's battery_history.csv)
+        log: Option<PathBuf>,
+        /// Only use readings from this product ID, e.g. 2034
+        #[arg(long, value_parser = parse_product_id)]
+        model: Option<u16>,
+        /// Number of points in the generated table
+        #[arg(long, default_value_t = 11)]
+        points: usize,
+    },
+    /// Show the configured text templates filled in for sample readings
+    PreviewTemplate {
+        /// Template to preview instead of the configured ones
+        template: Option<String>,
+    },
+    /// Check that every bundled translation has the same messages as en-US
+    CheckLocales,
+    /// Render the tray icon for a made-up reading, without a mouse or tray
+    RenderIcon(RenderIconArgs),
+    /// Print the status for a status bar, one line per change
+    Watch {
+        /// plain, waybar or i3bar
+        #[arg(long, default_value = "plain", value_parser = parse_setting::<BarFormat>)]
+        format: BarFormat,
+        /// Template for the text while a level is known
+        #[arg(long, default_value = status_bar::DEFAULT_TEXT)]
+        text: String,
+        /// Print the current status and exit
+        #[arg(long)]
+        once: bool,
+    },
+    /// Show, enable or disable starting at login through the XDG autostart entry
+    #[cfg(target_os = "linux")]
+    Autostart {
+        #[arg(default_value = "status", value_parser = ["status", "enable", "disable"])]
+        action: String,
+    },
+    /// Monitor in the background without the tray, e.g. as a systemd service.
+    /// SIGHUP reloads the settings.
+    #[cfg(target_os = "linux")]
+    Daemon,
+    /// Install a systemd user unit that runs the daemon
+    #[cfg(target_os = "linux")]
+    InstallService {
+        /// Also run daemon-reload and enable and start the unit
+        #[arg(long)]
+        enable: bool,
+        /// Print the unit instead of writing it
+        #[arg(long, conflicts_with = "enable")]
+        print: bool,
+    },
+}
+
+#[derive(Args)]
+struct RenderIconArgs {
+    /// discharging, charging, full, asleep, waking, not_found or unknown
+    #[arg(long, default_value = "discharging", value_parser = parse_status)]
+    status: String,
+    /// Battery level; for asleep, waking and unknown it is drawn as the
+    /// last known level
+    #[arg(long, value_parser = clap::value_parser!(u8).range(0..=100))]
+    percent: Option<u8>,
+    /// Panel colours to draw for: dark or light
+    #[arg(long, default_value = "dark", value_parser = parse_theme)]
+    theme: Theme,
+    /// Icon size in pixels
+    #[arg(long, default_value_t = 32, value_parser = clap::value_parser!(u32).range(8..=256))]
+    size: u32,
+    /// Icon style, instead of icon.style from the settings
+    #[arg(long, value_parser = parse_setting::<IconStyle>)]
+    style: Option<IconStyle>,
+    /// Product ID of the mouse, which picks the badges
+    #[arg(long, default_value = "2034", value_parser = parse_product_id)]
+    model: u16,
+    /// Animation step, e.g. 0 to 3 for the charging sweep
+    #[arg(long, default_value_t = 0)]
+    frame: u64,
+    /// Icon pack directory, instead of icon.pack from the settings
+    #[arg(long)]
+    pack: Option<PathBuf>,
+    /// Ignore settings.toml and use the default settings, so the
+    /// output only depends on the arguments
+    #[arg(long)]
+    defaults: bool,
+    /// PNG file to write (defaults to icon.png)
+    #[arg(long, short)]
+    output: Option<PathBuf>,
+    /// Compare with a reference PNG instead of writing one, and fail if
+    /// any pixel differs
+    #[arg(long, conflicts_with = "output")]
+    compare: Option<PathBuf>,
+}
+
+fn parse_product_id(value: &str) -> Result<u16, String> {
+    u16::from_str_radix(value.trim_start_matches("0x"), 16)
+        .map_err(|_| format!("invalid product ID: {}", value))
+}
+
+const STATUSES: &[&str] = &[
+    "discharging",
+    "charging",
+    "full",
+    "asleep",
+    "waking",
+    "not_found",
+    "unknown",
+];
+
+fn parse_status(value: &str) -> Result<String, String> {
+    if STATUSES.contains(&value) {
+        Ok(value.to_string())
+    } else {
+        Err(format!("expected one of {}", STATUSES.join(", ")))
+    }
+}
+
+fn parse_theme(value: &str) -> Result<Theme, String> {
+    match value {
+        "dark" => Ok(Theme::Dark),
+        "light" => Ok(Theme::Light),
+        _ => Err("expected dark or light".to_string()),
+    }
+}
+
+// Parses a value the way settings.toml spells it, e.g. "gauge_text"
+fn parse_setting<T: DeserializeOwned>(value: &str) -> Result<T, String> {
+    serde_json::from_value(serde_json::Value::String(value.to_string())).map_err(|e| e.to_string())
+}
+
+// Parses the command line. Launchers may pass arguments of their own, so
+// anything the CLI doesn't know starts the tray app (None) unless it comes
+// after a subcommand; help, version and subcommand errors are returned.
+fn parse_args(args: Vec<OsString>) -> Result<Option<Cli>, clap::Error> {
+    match Cli::try_parse_from(&args) {
+        Ok(cli) => Ok(Some(cli)),
+        Err(e) if !e.use_stderr() || names_subcommand(args.get(1)) => Err(e),
+        Err(_) => Ok(None),
+    }
+}
+
+fn names_subcommand(arg: Option<&OsString>) -> bool {
+    let Some(arg) = arg.and_then(|arg| arg.to_str()) else {
+        return false;
+    };
+    Cli::command()
+        .get_subcommands()
+        .any(|command| command.get_name() == arg || command.get_all_aliases().any(|a| a == arg))
+}
+
+// Runs a CLI subcommand if one was given and returns its exit code.
+// Returns None when the tray app should start instead.
+pub fn run_cli() -> Option<i32> {
+    let cli = match parse_args(std::env::args_os().collect()) {
+        Ok(cli) => cli?,
+        Err(e) => e.exit(),
+    };
+
+    let result = match cli.command? {
+        Command::Calibrate { log, model, points } => calibrate(log, model, points),
+        Command::PreviewTemplate { template } => preview_template(template),
+        Command::CheckLocales => check_locales(),
+        Command::RenderIcon(args) => render_icon(args),
+        Command::Watch { format, text, once } => watch(format, &text, once),
+        #[cfg(target_os = "linux")]
+        Command::Autostart { action } => autostart(&action),
+        #[cfg(target_os = "linux")]
+        Command::Daemon => daemon::run(),
+        #[cfg(target_os = "linux")]
+        Command::InstallService { enable, print } => install_service(enable, print),
+    };
+
+    Some(match result {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            1
+        }
+    })
+}
+
+fn calibrate(log: Option<PathBuf>, model: Option<u16>, points: usize) -> Result<(), String> {
+    let path = log
+        .or_else(history::history_path)
+        .ok_or("No history log found, pass a path")?;
+    let samples = history::read_log(&path, model)?;
+    let table = calibration::table_from_discharge(&samples, points)?;
+
+    let points: Vec<String> = table
+        .iter()
+        .map(|(raw, percent)| format!("[{}, {}]", raw, percent))
+        .collect();
+    let entries: Vec<String> = table
+        .iter()
+        .map(|(raw, percent)| format!("({}, {})", raw, percent))
+        .collect();
+    // The table only comes from one mouse's readings
+    let product_id = format!("{:04x}", samples[0].product_id);
+
+    println!("# settings.toml");
+    println!("[devices.calibration]");
+    println!("\"{}\" = [{}]", product_id, points.join(", "));
+    println!();
+    println!("// or the mouse's entry in SUPPORTED_MICE");
+    println!("calibration: &[{}],", entries.join(", "));
+    Ok(())
+}
+
+fn preview_template(text: Option<String>) -> Result<(), String> {
+    let samples = sample_readings();
+    let settings = settings::load_read_only()?;
+    i18n::set_language(&settings.language);
+
+    if let Some(text) = text {
+        template::validate(&text)?;
+        for (label, reading) in &samples {
+            println!("{:<24} {}", label, template::render(&text, reading));
+        }
+    } else {
+        for (label, reading) in &samples {
+            println!("{}", label);
+            println!("  icon:    {}", icon::icon_text(reading, &settings.icon));
+            println!(
+                "  tooltip: {}",
+                template::render_or(
+                    settings.icon.tooltip.as_deref(),
+                    reading,
+                    reading.get_tooltip()
+                )
+            );
+            println!(
+                "  menu:    {}",
+                template::render_or(
+                    settings.menu.status_text.as_deref(),
+                    reading,
+                    reading.get_tooltip()
+                )
+            );
+        }
+    }
+
+    println!();
+    println!("Placeholders:");
+    for name in template::PLACEHOLDERS {
+        let placeholder = format!("{{{}}}", name);
+        println!("  {:<13}{}", placeholder, template::describe(name));
+    }
+    Ok(())
+}
+
+fn sample_readings() -> Vec<(&'static str, BatteryReading)> {
+    let mouse_model = MouseModel::from_product_id(0x2034);
+    let reading = |status, last_known, remaining_mins: Option<u64>| BatteryReading {
+        status,
+        last_known,
+        remaining: remaining_mins.map(|mins| Duration::from_secs(mins * 60)),
+        firmware: Some("1.0.4.2".to_string()),
+        raw_percentage: None,
+    };
+    let asleep_since = SystemTime::now() - Duration::from_secs(14 * 60);
+
+    vec![
+        (
+            "Discharging at 62%",
+            reading(
+                BatteryStatus::Normal {
+                    percentage: 62,
+                    mouse_model,
+                },
+                None,
+                Some(31 * 60 + 20),
+            ),
+        ),
+        (
+            "Charging at 41%",
+            reading(
+                BatteryStatus::Charging {
+                    percentage: 41,
+                    mouse_model,
+                },
+                None,
+                Some(75),
+            ),
+        ),
+        (
+            "Fully charged",
+            reading(BatteryStatus::FullyCharged { mouse_model }, None, None),
+        ),
+        (
+            "Asleep, last seen at 58%",
+            reading(
+                BatteryStatus::Asleep { mouse_model },
+                Some(LastKnown {
+                    percentage: 58,
+                    charging: false,
+                    recorded_at: asleep_since,
+                }),
+                None,
+            ),
+        ),
+        ("Not found", reading(BatteryStatus::NotFound, None, None)),
+    ]
+}
+
+fn check_locales() -> Result<(), String> {
+    let problems = i18n::check_catalogs();
+    if problems.is_empty() {
+        println!("All locales complete: {}", i18n::available().join(", "));
+        return Ok(());
+    }
+    for problem in &problems {
+        println!("{}", problem);
+    }
+    Err(format!(
+        "{} problem(s) in the bundled locales",
+        problems.len()
+    ))
+}
+
+// Polls the mouse and prints the status whenever it changes, without
+// recording history, sending notifications or running hooks. Stops when
+// the bar closes its end of the pipe.
+fn watch(format: BarFormat, text: &str, once: bool) -> Result<(), String> {
+    template::validate(text).map_err(|e| format!("--text {}", e))?;
+    let settings = settings::load_read_only()?;
+    i18n::set_language(&settings.language);
+    // The tray app or the daemon usually runs next to the status bar and
+    // already sends the notifications, runs the hooks and keeps the history
+    let mut monitor = Monitor::read_only(MouseBattery::new()?, &settings);
+
+    // Follow settings.toml like the tray app
+    let (settings_tx, mut settings_rx) = tokio::sync::watch::channel(settings);
+    let _watcher = settings::watch(settings_tx, |e| log_error(&e))
+        .map_err(|e| log_error(&format!("Not watching the settings: {}", e)))
+        .ok();
+
+    let mut stdout = std::io::stdout();
+    if format == BarFormat::I3bar && writeln!(stdout, "{}", status_bar::I3BAR_HEADER).is_err() {
+        return Ok(());
+    }
+    let mut last_line = None;
+
+    loop {
+        if settings_rx.has_changed().unwrap_or(false) {
+            let settings = settings_rx.borrow_and_update();
+            i18n::set_language(&settings.language);
+            monitor.apply_settings(&settings);
+        }
+        let settings = settings_rx.borrow().clone();
+
+        let readings = monitor.read_all(&settings);
+        let line = BarStatus::new(&readings, &settings, text).line(format);
+        if last_line.as_ref() != Some(&line) {
+            if writeln!(stdout, "{}", line)
+                .and_then(|()| stdout.flush())
+                .is_err()
+            {
+                return Ok(());
+            }
+            last_line = Some(line);
+        }
+
+        if once {
+            return Ok(());
+        }
+        std::thread::sleep(settings.polling_interval());
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn autostart(action: &str) -> Result<(), String> {
+    let path = autostart::entry_path()?;
+    match action {
+        "enable" => autostart::enable_at(&path, &autostart::launch_path()?)?,
+        "disable" => autostart::disable_at(&path)?,
+        _ => {}
+    }
+
+    let state = if autostart::is_enabled_at(&path) {
+        "enabled"
+    } else {
+        "disabled"
+    };
+    println!("Autostart {} ({})", state, path.display());
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn install_service(enable: bool, print: bool) -> Result<(), String> {
+    // The AppImage when run from one, which outlives the unpacked binary
+    let exe = autostart::launch_path()?;
+    if print {
+        print!("{}", daemon::unit_file(&exe));
+        return Ok(());
+    }
+
+    let path = daemon::install_service(&exe, enable)?;
+    println!("Wrote {}", path.display());
+    if !enable {
+        println!("Start it with: systemctl --user daemon-reload && systemctl --user enable --now model-d2-pro-battery");
+    }
+    Ok(())
+}
+
+fn render_icon(args: RenderIconArgs) -> Result<(), String> {
+    let settings = if args.defaults {
+        settings::Settings::default()
+    } else {
+        settings::load_read_only()?
+    };
+    let img = draw_icon(&args, settings)?;
+
+    if let Some(reference) = args.compare {
+        compare_icon(&img, &reference)?;
+        println!("Matches {}", reference.display());
+        return Ok(());
+    }
+
+    let output = args.output.unwrap_or_else(|| PathBuf::from("icon.png"));
+    img.save(&output)
+        .map_err(|e| format!("Failed to write {}: {}", output.display(), e))?;
+    println!("Wrote {}", output.display());
+    Ok(())
+}
+
+// Draws the icon the arguments ask for with the given settings
+fn draw_icon(args: &RenderIconArgs, settings: settings::Settings) -> Result<RgbaImage, String> {
+    let mut icon_settings = settings.icon;
+    if let Some(style) = args.style {
+        icon_settings.style = style;
+    }
+    if args.pack.is_some() {
+        icon_settings.pack = args.pack.clone();
+    }
+
+    let pack = match icon_settings.pack.as_deref() {
+        Some(dir) => {
+            let dir = icon_pack::resolve_dir(dir).ok_or("No config directory available")?;
+            Some(IconPack::load(&dir)?)
+        }
+        None => None,
+    };
+
+    let reading = synthetic_reading(&args.status, args.percent, args.model);
+    let critical_threshold = settings.notifications.critical_threshold;
+    let frame = icon::animation_frame(&reading, &icon_settings, critical_threshold, args.frame);
+    icon::render_icon(
+        &reading,
+        &icon_settings,
+        args.size,
+        args.theme,
+        pack.as_ref(),
+        frame,
+    )
+}
+
+// Fails unless the icon equals the reference PNG pixel for pixel
+fn compare_icon(img: &RgbaImage, reference: &Path) -> Result<(), String> {
+    let expected = image::open(reference)
+        .map_err(|e| format!("Failed to read {}: {}", reference.display(), e))?
+        .to_rgba8();
+    if expected.dimensions() != img.dimensions() {
+        return Err(format!(
+            "{} is {}x{}, the icon is {}x{}",
+            reference.display(),
+            expected.width(),
+            expected.height(),
+            img.width(),
+            img.height()
+        ));
+    }
+    let differing = img
+        .pixels()
+        .zip(expected.pixels())
+        .filter(|(a, b)| a != b)
+        .count();
+    if differing > 0 {
+        return Err(format!(
+            "{} pixel(s) differ from {}",
+            differing,
+            reference.display()
+        ));
+    }
+    Ok(())
+}
+
+// Reading for a status name as used by icon packs and hooks. Statuses
+// without a live level show `percent` as the last known one.
+fn synthetic_reading(status: &str, percent: Option<u8>, product_id: u16) -> BatteryReading {
+    let mouse_model = MouseModel::from_product_id(product_id);
+    let percentage = percent.unwrap_or(50);
+    let status = match status {
+        "discharging" => BatteryStatus::Normal {
+            percentage,
+            mouse_model,
+        },
+        "charging" => BatteryStatus::Charging {
+            percentage,
+            mouse_model,
+        },
+        "full" => BatteryStatus::FullyCharged { mouse_model },
+        "asleep" => BatteryStatus::Asleep { mouse_model },
+        "waking" => BatteryStatus::WakingUp { mouse_model },
+        "not_found" => BatteryStatus::NotFound,
+        _ => BatteryStatus::Unknown {
+            raw_status: 0,
+            raw_battery: 0,
+            mouse_model,
+        },
+    };
+
+    let live = status.percentage().is_some() || matches!(status, BatteryStatus::NotFound);
+    let last_known = percent.filter(|_| !live).map(|percentage| LastKnown {
+        percentage,
+        charging: false,
+        recorded_at: SystemTime::now(),
+    });
+
+    BatteryReading {
+        status,
+        last_known,
+        remaining: None,
+        firmware: None,
+        raw_percentage: percent.filter(|_| live),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::icon::{Frame, ICON_SIZES};
+    use crate::settings::IconSettings;
+
+    // States with the level drawn for them, the last known one for asleep
+    const STATES: &[(&str, Option<u8>)] = &[
+        ("discharging", Some(60)),
+        ("charging", Some(40)),
+        ("full", None),
+        ("asleep", Some(15)),
+        ("waking", None),
+        ("not_found", None),
+        ("unknown", None),
+    ];
+    const STYLES: [IconStyle; 4] = [
+        IconStyle::Text,
+        IconStyle::Gauge,
+        IconStyle::GaugeText,
+        IconStyle::Png,
+    ];
+
+    // Renders every style, state and size with the default settings and
+    // compares it with tests/icons. After an intended change to the icons,
+    // run with UPDATE_ICON_REFERENCES=1 to write new references.
+    #[test]
+    fn icons_match_references() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/icons");
+        let update = std::env::var_os("UPDATE_ICON_REFERENCES").is_some();
+        let mut failures = Vec::new();
+
+        for style in STYLES {
+            let settings = IconSettings {
+                style,
+                ..IconSettings::default()
+            };
+            let style_name = serde_json::to_value(style).unwrap();
+            for (state, percent) in STATES {
+                let reading = synthetic_reading(state, *percent, 0x2034);
+                for &size in ICON_SIZES {
+                    let img = icon::render_icon(
+                        &reading,
+                        &settings,
+                        size,
+                        Theme::Dark,
+                        None,
+                        Frame::Still,
+                    )
+                    .unwrap();
+                    let path = dir.join(format!(
+                        "{}_{}_{}.png",
+                        style_name.as_str().unwrap(),
+                        state,
+                        size
+                    ));
+                    if update {
+                        img.save(&path).unwrap();
+                    } else if let Err(e) = compare_icon(&img, &path) {
+                        failures.push(e);
+                    }
+                }
+            }
+        }
+
+        assert!(failures.is_empty(), "{}", failures.join("\n"));
+    }
+
+    fn render_args(args: &[&str]) -> RenderIconArgs {
+        let args = ["app", "render-icon"].iter().chain(args);
+        match Cli::try_parse_from(args).unwrap().command {
+            Some(Command::RenderIcon(args)) => args,
+            _ => panic!("not a render-icon command"),
+        }
+    }
+
+    // Runs render-icon's arguments through the parser and compares the
+    // result with tests/icons, for a light panel and animation frames
+    #[test]
+    fn render_icon_arguments_match_references() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/icons");
+        let update = std::env::var_os("UPDATE_ICON_REFERENCES").is_some();
+        let mut animated = settings::Settings::default();
+        animated.icon.animate = true;
+        animated.icon.blink_critical = true;
+        animated.notifications.critical_threshold = 20;
+        let cases: &[(&[&str], &settings::Settings, &str)] = &[
+            (
+                &[
+                    "--percent",
+                    "60",
+                    "--theme",
+                    "light",
+                    "--style",
+                    "gauge_text",
+                    "--size",
+                    "24",
+                ],
+                &settings::Settings::default(),
+                "args_light_gauge_text_24.png",
+            ),
+            (
+                &[
+                    "--status",
+                    "charging",
+                    "--percent",
+                    "40",
+                    "--style",
+                    "gauge",
+                    "--frame",
+                    "2",
+                ],
+                &animated,
+                "args_charging_frame_2_32.png",
+            ),
+            (
+                &["--percent", "15", "--style", "gauge", "--frame", "1"],
+                &animated,
+                "args_blink_off_32.png",
+            ),
+        ];
+
+        let mut failures = Vec::new();
+        for (args, settings, name) in cases {
+            let img = draw_icon(&render_args(args), (*settings).clone()).unwrap();
+            let path = dir.join(name);
+            if update {
+                img.save(&path).unwrap();
+            } else if let Err(e) = compare_icon(&img, &path) {
+                failures.push(e);
+            }
+        }
+        assert!(failures.is_empty(), "{}", failures.join("\n"));
+    }
+
+    #[test]
+    fn render_icon_uses_the_critical_threshold_from_the_settings() {
+        let args = render_args(&["--percent", "15", "--frame", "1"]);
+        let mut settings = settings::Settings::default();
+        settings.icon.animate = true;
+        settings.icon.blink_critical = true;
+        let still = draw_icon(&args, settings.clone()).unwrap();
+        settings.notifications.critical_threshold = 20;
+        let blinking = draw_icon(&args, settings).unwrap();
+        assert_ne!(still, blinking);
+    }
+
+    fn parse(args: &[&str]) -> Result<Option<Cli>, clap::Error> {
+        parse_args(args.iter().map(OsString::from).collect())
+    }
+
+    #[test]
+    fn unknown_arguments_start_the_tray() {
+        // No arguments, the autostart flag and launcher extras start the tray
+        assert!(matches!(
+            parse(&["app"]),
+            Ok(Some(Cli { command: None, .. }))
+        ));
+        assert!(matches!(
+            parse(&["app", "--minimized"]),
+            Ok(Some(Cli {
+                command: None,
+                autostart: true
+            }))
+        ));
+        assert!(matches!(parse(&["app", "-psn_0_12345"]), Ok(None)));
+        assert!(matches!(parse(&["app", "--no-sandbox", "watch"]), Ok(None)));
+
+        // Subcommands run, and their mistakes are reported
+        assert!(matches!(
+            parse(&["app", "watch", "--once"]),
+            Ok(Some(Cli {
+                command: Some(Command::Watch { once: true, .. }),
+                ..
+            }))
+        ));
+        assert!(parse(&["app", "watch", "--bogus"]).is_err());
+        assert!(parse(&["app", "render-icon", "--size", "4"]).is_err());
+        assert!(parse(&["app", "--help"]).is_err());
+        assert!(parse(&["app", "--version"]).is_err());
+    }
+}
@@ -0,0 +1,87 @@
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+// Repeats of an action within this time are dropped, so the two clicks of
+// a double-click don't both poll the mouse
+const DEBOUNCE: Duration = Duration::from_millis(1000);
+
+// How long a single click waits for a second one when double-click has an
+// action of its own
+pub const DOUBLE_CLICK_WINDOW: Duration = Duration::from_millis(400);
+
+// What a click on the tray icon does, set per button in `[tray]`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ClickAction {
+    None,
+    // Poll the mouse now
+    Refresh,
+    // Notification with the status of every mouse
+    Popup,
+    // Open the tray menu; left click only, the system opens it
+    Menu,
+    // Switch notifications.enabled on or off
+    ToggleNotifications,
+    // Run hooks.on_click
+    RunHook,
+}
+
+impl ClickAction {
+    pub fn name(self) -> &'static str {
+        match self {
+            ClickAction::None => "none",
+            ClickAction::Refresh => "refresh",
+            ClickAction::Popup => "popup",
+            ClickAction::Menu => "menu",
+            ClickAction::ToggleNotifications => "toggle_notifications",
+            ClickAction::RunHook => "run_hook",
+        }
+    }
+}
+
+// Decides which click actions run. A single click can be held back until
+// it's clear that no second click follows.
+pub struct ClickDebouncer {
+    last: Option<(ClickAction, Instant)>,
+    // Bumped for every deferred click, so only the latest one may run
+    generation: u64,
+}
+
+impl ClickDebouncer {
+    pub fn new() -> Self {
+        Self {
+            last: None,
+            generation: 0,
+        }
+    }
+
+    // Whether `action` should run now, recording it if so
+    pub fn should_run(&mut self, action: ClickAction, now: Instant) -> bool {
+        if action == ClickAction::None {
+            return false;
+        }
+        if let Some((last, at)) = self.last {
+            if last == action && now.duration_since(at) < DEBOUNCE {
+                return false;
+            }
+        }
+        self.last = Some((action, now));
+        true
+    }
+
+    // Holds back a single click; pass the token to `is_current` once
+    // DOUBLE_CLICK_WINDOW has passed
+    pub fn defer(&mut self) -> u64 {
+        self.generation += 1;
+        self.generation
+    }
+
+    // Drops the click held back, e.g. because a double-click arrived
+    pub fn cancel(&mut self) {
+        self.generation += 1;
+    }
+
+    pub fn is_current(&self, token: u64) -> bool {
+        self.generation == token
+    }
+}
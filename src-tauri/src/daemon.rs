@@ -0,0 +1,304 @@
+use crate::clicks::{ClickAction, ClickDebouncer};
+use crate::last_known::BatteryReading;
+use crate::monitor::Monitor;
+use crate::mouse_battery::MouseBattery;
+use crate::settings::{self, Settings};
+use crate::{dbus_service, http_api, i18n, log_error, tooltip_text};
+use std::ffi::OsStr;
+use std::fs;
+use std::os::linux::net::SocketAddrExt;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::net::{SocketAddr, UnixDatagram};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{Duration, Instant};
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::{mpsc, watch};
+use tokio::time::{interval, Interval};
+
+const UNIT_NAME: &str = "model-d2-pro-battery.service";
+
+// Runs the monitor without the tray until SIGTERM or Ctrl+C. Readings are
+// logged when they change and alerts and hooks work as in the tray app;
+// SIGHUP reloads settings.toml. The readings are published on D-Bus and
+// the HTTP API as in the tray app.
+pub fn run() -> Result<(), String> {
+    let settings = settings::load()?;
+    i18n::set_language(&settings.language);
+    let monitor = Monitor::new(MouseBattery::new()?, &settings);
+
+    // Multi-threaded so the D-Bus and HTTP tasks keep answering while a
+    // poll waits on the mouse
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .map_err(|e| format!("Failed to start runtime: {}", e))?;
+    runtime.block_on(monitor_loop(monitor, settings))
+}
+
+async fn monitor_loop(mut monitor: Monitor, mut settings: Settings) -> Result<(), String> {
+    let listen =
+        |kind: SignalKind| signal(kind).map_err(|e| format!("Failed to listen for signals: {}", e));
+    let mut sigterm = listen(SignalKind::terminate())?;
+    let mut sigint = listen(SignalKind::interrupt())?;
+    let mut sighup = listen(SignalKind::hangup())?;
+
+    let readings_tx = watch::Sender::new(Vec::new());
+    // Holds one pending refresh; more requests meanwhile are merged
+    let (refresh_tx, mut refresh_rx) = mpsc::channel(1);
+    let mut refreshes = ClickDebouncer::new();
+    let settings_tx = watch::Sender::new(settings.clone());
+    tokio::spawn(http_api::serve(
+        settings_tx.subscribe(),
+        readings_tx.subscribe(),
+        refresh_tx.clone(),
+    ));
+    tokio::spawn(dbus_service::serve(
+        None,
+        settings_tx.subscribe(),
+        readings_tx.subscribe(),
+        refresh_tx,
+    ));
+
+    let mut poll = interval(settings.polling_interval());
+    let mut watchdog = watchdog_interval().map(interval);
+    let mut last_lines = Vec::new();
+    let mut ready = false;
+
+    loop {
+        tokio::select! {
+            _ = poll.tick() => {
+                read(&mut monitor, &settings, &readings_tx, &mut last_lines);
+                // Ready once the first reading is in
+                if !ready {
+                    sd_notify("READY=1");
+                    ready = true;
+                }
+            }
+            Some(()) = refresh_rx.recv() => {
+                // Refresh() over D-Bus or the HTTP API, debounced like a
+                // refresh click in the tray app
+                if refreshes.should_run(ClickAction::Refresh, Instant::now()) {
+                    read(&mut monitor, &settings, &readings_tx, &mut last_lines);
+                }
+            }
+            _ = tick(&mut watchdog) => {
+                sd_notify("WATCHDOG=1");
+            }
+            _ = sighup.recv() => {
+                sd_notify("RELOADING=1");
+                match settings::load() {
+                    Ok(new_settings) => {
+                        i18n::set_language(&new_settings.language);
+                        monitor.apply_settings(&new_settings);
+                        if new_settings.polling_interval() != settings.polling_interval() {
+                            poll = interval(new_settings.polling_interval());
+                        }
+                        settings = new_settings;
+                        settings_tx.send_replace(settings.clone());
+                        log_error("Settings reloaded");
+                    }
+                    Err(e) => log_error(&format!(
+                        "Failed to reload settings, keeping the previous ones: {}",
+                        e
+                    )),
+                }
+                sd_notify("READY=1");
+            }
+            _ = sigterm.recv() => break,
+            _ = sigint.recv() => break,
+        }
+    }
+
+    sd_notify("STOPPING=1");
+    Ok(())
+}
+
+// Reads every mouse, publishes the readings and logs them when they changed
+fn read(
+    monitor: &mut Monitor,
+    settings: &Settings,
+    readings_tx: &watch::Sender<Vec<BatteryReading>>,
+    last_lines: &mut Vec<String>,
+) {
+    // The HID reads block; hand this worker's other tasks to another thread
+    let readings = tokio::task::block_in_place(|| monitor.read_all(settings));
+    let lines: Vec<String> = readings
+        .iter()
+        .map(|reading| tooltip_text(reading, settings))
+        .collect();
+    readings_tx.send_replace(readings);
+    if lines != *last_lines {
+        for line in &lines {
+            log_error(line);
+        }
+        sd_notify(&format!("STATUS={}", lines.join("; ")));
+        *last_lines = lines;
+    }
+}
+
+// Waits for the next tick, or forever without an interval
+async fn tick(interval: &mut Option<Interval>) {
+    match interval {
+        Some(interval) => {
+            interval.tick().await;
+        }
+        None => std::future::pending().await,
+    }
+}
+
+// How often to tell systemd we're alive: half the WatchdogSec it set, if
+// the watchdog is meant for this process
+fn watchdog_interval() -> Option<Duration> {
+    watchdog_period(
+        std::env::var("WATCHDOG_PID").ok().as_deref(),
+        std::env::var("WATCHDOG_USEC").ok().as_deref(),
+        std::process::id(),
+    )
+}
+
+// watchdog_interval for the given WATCHDOG_PID and WATCHDOG_USEC values
+fn watchdog_period(pid: Option<&str>, usec: Option<&str>, own_pid: u32) -> Option<Duration> {
+    if let Some(pid) = pid {
+        if pid.parse::<u32>().ok() != Some(own_pid) {
+            return None;
+        }
+    }
+    let usec: u64 = usec?.parse().ok()?;
+    Some(Duration::from_micros(usec) / 2).filter(|period| !period.is_zero())
+}
+
+// Sends a state change to systemd when it started us with Type=notify
+fn sd_notify(state: &str) {
+    let Some(socket_path) = std::env::var_os("NOTIFY_SOCKET") else {
+        return;
+    };
+    let result = UnixDatagram::unbound().and_then(|socket| {
+        match socket_path.as_bytes().strip_prefix(b"@") {
+            // Linux abstract socket
+            Some(name) => SocketAddr::from_abstract_name(name)
+                .and_then(|addr| socket.send_to_addr(state.as_bytes(), &addr)),
+            None => socket.send_to(state.as_bytes(), Path::new(&socket_path)),
+        }
+    });
+    if let Err(e) = result {
+        log_error(&format!("Failed to notify systemd: {}", e));
+    }
+}
+
+// $XDG_CONFIG_HOME/systemd/user/model-d2-pro-battery.service
+pub fn unit_path() -> Result<PathBuf, String> {
+    dirs::config_dir()
+        .map(|dir| dir.join("systemd").join("user").join(UNIT_NAME))
+        .ok_or_else(|| "No config directory available".to_string())
+}
+
+pub fn unit_file(exe: &Path) -> String {
+    format!(
+        "[Unit]\n\
+         Description=Glorious mouse battery monitor\n\
+         \n\
+         [Service]\n\
+         Type=notify\n\
+         ExecStart={} daemon\n\
+         ExecReload=/bin/kill -HUP $MAINPID\n\
+         WatchdogSec=60\n\
+         Restart=on-failure\n\
+         RestartSec=10\n\
+         \n\
+         [Install]\n\
+         WantedBy=default.target\n",
+        quote_exec(exe.as_os_str())
+    )
+}
+
+// Quotes a path for ExecStart, escaping systemd's % and $ expansion
+fn quote_exec(arg: &OsStr) -> String {
+    let arg = arg.to_string_lossy().replace('%', "%%").replace('$', "$$");
+    if !arg.contains(|c: char| c.is_whitespace() || c == '"' || c == '\\') {
+        return arg;
+    }
+    format!("\"{}\"", arg.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+// Writes the user unit for `daemon`, optionally enabling and starting it
+pub fn install_service(exe: &Path, enable: bool) -> Result<PathBuf, String> {
+    let path = unit_path()?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    }
+    fs::write(&path, unit_file(exe))
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+
+    if enable {
+        systemctl(&["daemon-reload"])?;
+        systemctl(&["enable", "--now", UNIT_NAME])?;
+    }
+    Ok(path)
+}
+
+fn systemctl(args: &[&str]) -> Result<(), String> {
+    let status = Command::new("systemctl")
+        .arg("--user")
+        .args(args)
+        .status()
+        .map_err(|e| format!("Failed to run systemctl: {}", e))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!(
+            "systemctl --user {} failed ({})",
+            args.join(" "),
+            status
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn exec_start(exe: &str) -> String {
+        unit_file(Path::new(exe))
+            .lines()
+            .find_map(|line| line.strip_prefix("ExecStart="))
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn unit_file_quotes_exec_start() {
+        assert_eq!(exec_start("/usr/bin/battery"), "/usr/bin/battery daemon");
+        assert_eq!(
+            exec_start("/home/me/My Apps/battery"),
+            r#""/home/me/My Apps/battery" daemon"#
+        );
+        assert_eq!(
+            exec_start("/opt/100%/$HOME/battery"),
+            "/opt/100%%/$$HOME/battery daemon"
+        );
+        assert_eq!(exec_start(r#"/opt/a "b"\c"#), r#""/opt/a \"b\"\\c" daemon"#);
+    }
+
+    #[test]
+    fn watchdog_period_is_half_of_watchdog_usec() {
+        assert_eq!(
+            watchdog_period(None, Some("60000000"), 42),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            watchdog_period(Some("42"), Some("60000000"), 42),
+            Some(Duration::from_secs(30))
+        );
+    }
+
+    #[test]
+    fn watchdog_period_ignores_other_processes_and_bad_values() {
+        assert_eq!(watchdog_period(Some("7"), Some("60000000"), 42), None);
+        assert_eq!(watchdog_period(Some("x"), Some("60000000"), 42), None);
+        assert_eq!(watchdog_period(None, None, 42), None);
+        assert_eq!(watchdog_period(None, Some("soon"), 42), None);
+        assert_eq!(watchdog_period(None, Some("0"), 42), None);
+    }
+}
@@ -0,0 +1,482 @@
+use crate::last_known::BatteryReading;
+use crate::mouse_battery::BatteryStatus;
+use crate::settings::Settings;
+use crate::upower::UPowerDevice;
+use std::collections::BTreeMap;
+use std::time::Duration;
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::{mpsc, watch};
+use zbus::object_server::SignalEmitter;
+use zbus::zvariant::{ObjectPath, OwnedObjectPath};
+use zbus::{connection, fdo, Connection};
+
+// Session bus name, and the manager object listing the devices. Each mouse
+// is an object below DEVICES_PATH named after its product ID, e.g.
+// /io/github/GloriousBattery/devices/2034. With dbus.upower set, the
+// objects also implement org.freedesktop.UPower.Device.
+pub const BUS_NAME: &str = "io.github.GloriousBattery";
+const MANAGER_PATH: &str = "/io/github/GloriousBattery";
+const DEVICES_PATH: &str = "/io/github/GloriousBattery/devices";
+
+// Publishes the readings on the bus at `address`, or the session bus when
+// None, until `readings` or `settings` closes. `refresh` receives a message
+// for Refresh() calls; calls made while one is still pending are merged.
+pub async fn serve(
+    address: Option<String>,
+    mut settings: watch::Receiver<Settings>,
+    mut readings: watch::Receiver<Vec<BatteryReading>>,
+    refresh: mpsc::Sender<()>,
+) {
+    if let Err(e) = publish(address.as_deref(), &mut settings, &mut readings, refresh).await {
+        eprintln!("D-Bus service unavailable: {}", e);
+    }
+}
+
+async fn publish(
+    address: Option<&str>,
+    settings: &mut watch::Receiver<Settings>,
+    readings: &mut watch::Receiver<Vec<BatteryReading>>,
+    refresh: mpsc::Sender<()>,
+) -> zbus::Result<()> {
+    let manager = Manager {
+        devices: Vec::new(),
+        refresh,
+    };
+    let builder = match address {
+        Some(address) => connection::Builder::address(address)?,
+        None => connection::Builder::session()?,
+    };
+    // A second instance, e.g. the daemon next to the tray app, leaves the
+    // name with the first one
+    let connection = builder
+        .name(BUS_NAME)?
+        .allow_name_replacements(false)
+        .replace_existing_names(false)
+        .serve_at(MANAGER_PATH, manager)?
+        .build()
+        .await?;
+    let mut published = BTreeMap::new();
+    let mut mirrored = false;
+
+    loop {
+        let upower = settings.borrow_and_update().dbus.upower;
+        if upower != mirrored {
+            set_mirror(&connection, &published, upower).await?;
+            mirrored = upower;
+        }
+        let current = readings.borrow_and_update().clone();
+        update(&connection, &mut published, &current, mirrored).await?;
+
+        let changed = tokio::select! {
+            changed = readings.changed() => changed,
+            changed = settings.changed() => changed,
+        };
+        if changed.is_err() {
+            return Ok(());
+        }
+    }
+}
+
+// Adds or removes the UPower interface on every published device
+async fn set_mirror(
+    connection: &Connection,
+    published: &BTreeMap<String, DeviceProperties>,
+    upower: bool,
+) -> zbus::Result<()> {
+    let server = connection.object_server();
+    for (path, props) in published {
+        if upower {
+            server.at(path.as_str(), upower_device(path, props)).await?;
+        } else {
+            server.remove::<UPowerDevice, _>(path.as_str()).await?;
+        }
+    }
+    Ok(())
+}
+
+fn upower_device(path: &str, props: &DeviceProperties) -> UPowerDevice {
+    let native_path = path.replace(&format!("{}/", DEVICES_PATH), "glorious-");
+    UPowerDevice::new(native_path, props.clone())
+}
+
+// What a device object shows
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeviceProperties {
+    // Level shown in the tray, including the last known one; -1 when unknown
+    pub percentage: i32,
+    pub state: String,
+    pub model: String,
+    // Empty until the version has been read from the mouse
+    pub firmware: String,
+    pub is_wired: bool,
+    // Estimated time until empty, or full while charging
+    pub remaining: Option<Duration>,
+}
+
+impl DeviceProperties {
+    fn from_reading(reading: &BatteryReading) -> Self {
+        let mouse_model = reading.status.get_mouse_model();
+        Self {
+            percentage: reading.level().map(i32::from).unwrap_or(-1),
+            state: reading.status.state_name().to_string(),
+            model: reading.status.display_name(),
+            firmware: reading.firmware.clone().unwrap_or_default(),
+            is_wired: mouse_model.is_some_and(|m| m.is_wired()),
+            remaining: reading.remaining,
+        }
+    }
+}
+
+fn device_path(reading: &BatteryReading) -> Option<String> {
+    Some(format!("{}/{}", DEVICES_PATH, reading.device_id()?))
+}
+
+// Adds and removes device objects and sends PropertiesChanged for the
+// properties that differ from what was published before. The UPower
+// interface is kept in step while `upower` is set.
+async fn update(
+    connection: &Connection,
+    published: &mut BTreeMap<String, DeviceProperties>,
+    readings: &[BatteryReading],
+    upower: bool,
+) -> zbus::Result<()> {
+    let server = connection.object_server();
+    let current: BTreeMap<String, DeviceProperties> = readings
+        .iter()
+        .filter(|reading| !matches!(reading.status, BatteryStatus::NotFound))
+        .filter_map(|reading| {
+            Some((
+                device_path(reading)?,
+                DeviceProperties::from_reading(reading),
+            ))
+        })
+        .collect();
+
+    let manager = server.interface::<_, Manager>(MANAGER_PATH).await?;
+    let emitter = manager.signal_emitter();
+
+    for path in published.keys().filter(|path| !current.contains_key(*path)) {
+        server.remove::<Device, _>(path.as_str()).await?;
+        if upower {
+            server.remove::<UPowerDevice, _>(path.as_str()).await?;
+        }
+        Manager::device_removed(emitter, ObjectPath::try_from(path.as_str())?).await?;
+    }
+
+    for (path, props) in &current {
+        let Some(old) = published.get(path) else {
+            let device = Device {
+                props: props.clone(),
+            };
+            server.at(path.as_str(), device).await?;
+            if upower {
+                server.at(path.as_str(), upower_device(path, props)).await?;
+            }
+            Manager::device_added(emitter, ObjectPath::try_from(path.as_str())?).await?;
+            continue;
+        };
+        if old == props {
+            continue;
+        }
+
+        let device_ref = server.interface::<_, Device>(path.as_str()).await?;
+        let mut device = device_ref.get_mut().await;
+        device.props = props.clone();
+        let emitter = device_ref.signal_emitter();
+        if old.percentage != props.percentage {
+            device.percentage_changed(emitter).await?;
+        }
+        if old.state != props.state {
+            device.state_changed(emitter).await?;
+        }
+        if old.model != props.model {
+            device.model_changed(emitter).await?;
+        }
+        if old.firmware != props.firmware {
+            device.firmware_changed(emitter).await?;
+        }
+        if old.is_wired != props.is_wired {
+            device.is_wired_changed(emitter).await?;
+        }
+
+        if upower {
+            let upower_ref = server.interface::<_, UPowerDevice>(path.as_str()).await?;
+            let mut mirror = upower_ref.get_mut().await;
+            mirror
+                .update(props.clone(), upower_ref.signal_emitter())
+                .await?;
+        }
+    }
+
+    manager.get_mut().await.devices = current
+        .keys()
+        .map(|path| OwnedObjectPath::try_from(path.as_str()))
+        .collect::<Result<_, _>>()?;
+    *published = current;
+    Ok(())
+}
+
+struct Manager {
+    devices: Vec<OwnedObjectPath>,
+    refresh: mpsc::Sender<()>,
+}
+
+#[zbus::interface(name = "io.github.GloriousBattery.Manager")]
+impl Manager {
+    // Asks for a new reading of every mouse. Returns right away; the new
+    // values arrive as PropertiesChanged.
+    fn refresh(&self) -> fdo::Result<()> {
+        match self.refresh.try_send(()) {
+            // A refresh that is already pending covers this one as well
+            Ok(()) | Err(TrySendError::Full(())) => Ok(()),
+            Err(TrySendError::Closed(())) => {
+                Err(fdo::Error::Failed("Monitoring has stopped".to_string()))
+            }
+        }
+    }
+
+    fn list_devices(&self) -> Vec<OwnedObjectPath> {
+        self.devices.clone()
+    }
+
+    #[zbus(signal)]
+    async fn device_added(emitter: &SignalEmitter<'_>, device: ObjectPath<'_>) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn device_removed(
+        emitter: &SignalEmitter<'_>,
+        device: ObjectPath<'_>,
+    ) -> zbus::Result<()>;
+}
+
+struct Device {
+    props: DeviceProperties,
+}
+
+#[zbus::interface(name = "io.github.GloriousBattery.Device")]
+impl Device {
+    #[zbus(property)]
+    fn percentage(&self) -> i32 {
+        self.props.percentage
+    }
+
+    // discharging, charging, full, asleep, waking or unknown
+    #[zbus(property)]
+    fn state(&self) -> &str {
+        &self.props.state
+    }
+
+    #[zbus(property)]
+    fn model(&self) -> &str {
+        &self.props.model
+    }
+
+    #[zbus(property)]
+    fn firmware(&self) -> &str {
+        &self.props.firmware
+    }
+
+    #[zbus(property)]
+    fn is_wired(&self) -> bool {
+        self.props.is_wired
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mouse_battery::MouseModel;
+    use futures_util::StreamExt;
+    use std::io::{BufRead, BufReader};
+    use std::process::{Child, Command, Stdio};
+    use tokio::time::{sleep, timeout};
+    use zbus::names::InterfaceName;
+
+    #[zbus::proxy(
+        interface = "io.github.GloriousBattery.Manager",
+        default_service = "io.github.GloriousBattery",
+        default_path = "/io/github/GloriousBattery",
+        gen_blocking = false
+    )]
+    trait Manager {
+        fn refresh(&self) -> zbus::Result<()>;
+        fn list_devices(&self) -> zbus::Result<Vec<OwnedObjectPath>>;
+    }
+
+    #[zbus::proxy(
+        interface = "io.github.GloriousBattery.Device",
+        default_service = "io.github.GloriousBattery",
+        gen_blocking = false
+    )]
+    trait Device {
+        #[zbus(property)]
+        fn percentage(&self) -> zbus::Result<i32>;
+        #[zbus(property)]
+        fn state(&self) -> zbus::Result<String>;
+        #[zbus(property)]
+        fn is_wired(&self) -> zbus::Result<bool>;
+    }
+
+    // A private bus, stopped when dropped
+    struct Bus(Child);
+
+    impl Drop for Bus {
+        fn drop(&mut self) {
+            let _ = self.0.kill();
+            let _ = self.0.wait();
+        }
+    }
+
+    fn start_bus() -> Option<(Bus, String)> {
+        let child = Command::new("dbus-daemon")
+            .args(["--session", "--nofork", "--print-address=1"])
+            .stdout(Stdio::piped())
+            .spawn()
+            .ok()?;
+        let mut bus = Bus(child);
+        let mut address = String::new();
+        BufReader::new(bus.0.stdout.take()?)
+            .read_line(&mut address)
+            .ok()?;
+        Some((bus, address.trim().to_string()))
+    }
+
+    fn reading(status: BatteryStatus) -> BatteryReading {
+        BatteryReading {
+            status,
+            last_known: None,
+            remaining: None,
+            firmware: None,
+            raw_percentage: None,
+        }
+    }
+
+    fn readings(wireless: u8) -> Vec<BatteryReading> {
+        vec![
+            reading(BatteryStatus::Normal {
+                percentage: wireless,
+                mouse_model: MouseModel::from_product_id(0x2034),
+            }),
+            reading(BatteryStatus::Charging {
+                percentage: 40,
+                mouse_model: MouseModel::from_product_id(0x2011),
+            }),
+        ]
+    }
+
+    async fn upower_type(properties: &fdo::PropertiesProxy<'_>) -> Option<u32> {
+        let interface = InterfaceName::from_static_str_unchecked("org.freedesktop.UPower.Device");
+        let value = properties.get(interface, "Type").await.ok()?;
+        u32::try_from(value).ok()
+    }
+
+    #[tokio::test]
+    async fn publishes_readings() {
+        let Some((_bus, address)) = start_bus() else {
+            eprintln!("dbus-daemon not available, skipping");
+            return;
+        };
+        let (readings_tx, readings_rx) = watch::channel(readings(62));
+        let (refresh_tx, mut refresh_rx) = mpsc::channel(1);
+        let (settings_tx, settings_rx) = watch::channel(Settings::default());
+        let service = tokio::spawn(serve(
+            Some(address.clone()),
+            settings_rx,
+            readings_rx,
+            refresh_tx,
+        ));
+
+        let connection = connection::Builder::address(address.as_str())
+            .unwrap()
+            .build()
+            .await
+            .unwrap();
+        let manager = ManagerProxy::new(&connection).await.unwrap();
+        // The service owns the name once its objects are in place
+        let devices = timeout(Duration::from_secs(5), async {
+            loop {
+                if let Ok(devices) = manager.list_devices().await {
+                    return devices;
+                }
+                sleep(Duration::from_millis(20)).await;
+            }
+        })
+        .await
+        .expect("service did not come up");
+        let wireless_path = format!("{}/2034", DEVICES_PATH);
+        let wired_path = format!("{}/2011", DEVICES_PATH);
+        let devices: Vec<&str> = devices.iter().map(|path| path.as_str()).collect();
+        assert_eq!(devices, [wired_path.as_str(), wireless_path.as_str()]);
+
+        let wireless = DeviceProxy::builder(&connection)
+            .path(wireless_path.as_str())
+            .unwrap()
+            .build()
+            .await
+            .unwrap();
+        assert_eq!(wireless.percentage().await.unwrap(), 62);
+        assert_eq!(wireless.state().await.unwrap(), "discharging");
+        assert!(!wireless.is_wired().await.unwrap());
+        let wired = DeviceProxy::builder(&connection)
+            .path(wired_path.as_str())
+            .unwrap()
+            .build()
+            .await
+            .unwrap();
+        assert_eq!(wired.percentage().await.unwrap(), 40);
+        assert_eq!(wired.state().await.unwrap(), "charging");
+        assert!(wired.is_wired().await.unwrap());
+
+        // Only the level of the wireless mouse changes
+        let properties = fdo::PropertiesProxy::builder(&connection)
+            .destination(BUS_NAME)
+            .unwrap()
+            .path(wireless_path.as_str())
+            .unwrap()
+            .build()
+            .await
+            .unwrap();
+        let mut changes = properties.receive_properties_changed().await.unwrap();
+        readings_tx.send_replace(readings(61));
+        let signal = timeout(Duration::from_secs(5), changes.next())
+            .await
+            .expect("no PropertiesChanged")
+            .unwrap();
+        let args = signal.args().unwrap();
+        assert_eq!(
+            args.interface_name.as_str(),
+            "io.github.GloriousBattery.Device"
+        );
+        let changed: Vec<&str> = args.changed_properties.keys().copied().collect();
+        assert_eq!(changed, ["Percentage"]);
+        assert_eq!(
+            i32::try_from(&args.changed_properties["Percentage"]),
+            Ok(61)
+        );
+
+        // The UPower interface only while dbus.upower is set
+        assert_eq!(upower_type(&properties).await, None);
+        settings_tx.send_modify(|settings| settings.dbus.upower = true);
+        let mirrored = timeout(Duration::from_secs(5), async {
+            loop {
+                if let Some(device_type) = upower_type(&properties).await {
+                    return device_type;
+                }
+                sleep(Duration::from_millis(20)).await;
+            }
+        })
+        .await
+        .expect("UPower interface not added");
+        assert_eq!(mirrored, 5);
+
+        manager.refresh().await.unwrap();
+        let refreshed = timeout(Duration::from_secs(5), refresh_rx.recv()).await;
+        assert_eq!(refreshed, Ok(Some(())));
+
+        drop(readings_tx);
+        timeout(Duration::from_secs(5), service)
+            .await
+            .expect("service did not stop")
+            .unwrap();
+    }
+}
@@ -0,0 +1,99 @@
+use crate::i18n;
+use crate::mouse_battery::{BatteryStatus, MouseModel};
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
+
+// A rate is only trusted once the level has moved this far over at least
+// this long, so the first readings after a plug or unplug don't swing wildly
+const MIN_CHANGE: u8 = 2;
+const MIN_ELAPSED: Duration = Duration::from_secs(10 * 60);
+
+// Start of the current charge or discharge run
+#[derive(Debug, Clone, Copy)]
+struct Run {
+    charging: bool,
+    level: u8,
+    started_at: SystemTime,
+}
+
+// Per-device estimate of the time left, from the average rate since the
+// mouse was last plugged in or unplugged
+#[derive(Debug, Default)]
+pub struct RemainingEstimator {
+    runs: HashMap<MouseModel, Run>,
+}
+
+impl RemainingEstimator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Time until empty, or until full while charging. Readings without a
+    // live level keep the current run but give no estimate.
+    pub fn observe(&mut self, status: &BatteryStatus) -> Option<Duration> {
+        let (level, charging) = match status {
+            BatteryStatus::Normal { percentage, .. } => (*percentage, false),
+            BatteryStatus::Charging { percentage, .. } => (*percentage, true),
+            BatteryStatus::FullyCharged { .. } => (100, true),
+            _ => return None,
+        };
+        let mouse_model = status.get_mouse_model()?;
+        let now = SystemTime::now();
+        let new_run = Run {
+            charging,
+            level,
+            started_at: now,
+        };
+
+        let run = self.runs.entry(mouse_model).or_insert(new_run);
+        if run.charging != charging {
+            *run = new_run;
+            return None;
+        }
+
+        // Progress in the direction of the run. A small step back is read
+        // noise; a larger one means the run's start was off, so restart.
+        let change = if charging {
+            level as i16 - run.level as i16
+        } else {
+            run.level as i16 - level as i16
+        };
+        if change <= -(MIN_CHANGE as i16) {
+            *run = new_run;
+            return None;
+        }
+
+        let elapsed = now.duration_since(run.started_at).unwrap_or_default();
+        if change < MIN_CHANGE as i16 || elapsed < MIN_ELAPSED {
+            return None;
+        }
+
+        let left = if charging { 100 - level } else { level };
+        let secs_per_percent = elapsed.as_secs_f64() / change as f64;
+        Some(Duration::from_secs_f64(secs_per_percent * left as f64))
+    }
+}
+
+// Compact duration, e.g. "45 min", "3 h 20 min", "2 d 4 h"
+pub fn format_duration(duration: Duration) -> String {
+    let mins = duration.as_secs() / 60;
+    if mins < 60 {
+        i18n::tr_with("duration-minutes", &[("minutes", mins.into())])
+    } else if mins < 24 * 60 {
+        i18n::tr_with(
+            "duration-hours",
+            &[
+                ("hours", (mins / 60).into()),
+                ("minutes", (mins % 60).into()),
+            ],
+        )
+    } else {
+        i18n::tr_with(
+            "duration-days",
+            &[
+                ("days", (mins / (24 * 60)).into()),
+                ("hours", (mins / 60 % 24).into()),
+            ],
+        )
+    }
+}
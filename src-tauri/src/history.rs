@@ -0,0 +1,110 @@
+use crate::mouse_battery::BatteryStatus;
+use crate::paths;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const HISTORY_FILE: &str = "battery_history.csv";
+const HISTORY_HEADER: &str = "timestamp,product_id,raw,charging";
+// Rotate the history once it grows past this size
+const MAX_HISTORY_BYTES: u64 = 5 * 1024 * 1024;
+
+// One line of the battery history log: "timestamp,product_id,raw,charging"
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LogSample {
+    pub timestamp: u64,
+    pub product_id: u16,
+    pub raw: u8,
+    pub charging: bool,
+}
+
+impl LogSample {
+    pub fn now(product_id: u16, raw: u8, charging: bool) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        Self {
+            timestamp,
+            product_id,
+            raw,
+            charging,
+        }
+    }
+
+    pub fn parse(line: &str) -> Option<Self> {
+        let mut fields = line.trim().split(',');
+        let timestamp = fields.next()?.trim().parse().ok()?;
+        let product_id =
+            u16::from_str_radix(fields.next()?.trim().trim_start_matches("0x"), 16).ok()?;
+        let raw = fields.next()?.trim().parse().ok()?;
+        let charging = fields.next()?.trim() == "1";
+        Some(Self {
+            timestamp,
+            product_id,
+            raw,
+            charging,
+        })
+    }
+
+    fn to_line(self) -> String {
+        format!(
+            "{},{:04x},{},{}",
+            self.timestamp, self.product_id, self.raw, self.charging as u8
+        )
+    }
+}
+
+pub fn history_path() -> Option<PathBuf> {
+    paths::log_dir().map(|dir| dir.join(HISTORY_FILE))
+}
+
+// Appends a raw reading to the battery history
+pub fn append(sample: LogSample) -> Result<(), String> {
+    let path = history_path().ok_or("No log directory available")?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("Failed to create log directory: {}", e))?;
+    }
+
+    if fs::metadata(&path).is_ok_and(|m| m.len() > MAX_HISTORY_BYTES) {
+        let _ = fs::rename(&path, path.with_extension("csv.1"));
+    }
+
+    let is_new = !path.exists();
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+
+    if is_new {
+        writeln!(file, "{}", HISTORY_HEADER).map_err(|e| e.to_string())?;
+    }
+    writeln!(file, "{}", sample.to_line()).map_err(|e| e.to_string())
+}
+
+// Records a live reading; statuses without a level are skipped
+pub fn record(status: &BatteryStatus, raw: Option<u8>) -> Result<(), String> {
+    let charging = match status {
+        BatteryStatus::Normal { .. } => false,
+        BatteryStatus::Charging { .. } | BatteryStatus::FullyCharged { .. } => true,
+        _ => return Ok(()),
+    };
+
+    match (status.get_mouse_model().and_then(|m| m.product_id()), raw) {
+        (Some(product_id), Some(raw)) => append(LogSample::now(product_id, raw, charging)),
+        _ => Ok(()),
+    }
+}
+
+pub fn read_log(path: &Path, product_id: Option<u16>) -> Result<Vec<LogSample>, String> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+
+    Ok(content
+        .lines()
+        .filter_map(LogSample::parse)
+        .filter(|s| product_id.is_none_or(|pid| s.product_id == pid))
+        .collect())
+}
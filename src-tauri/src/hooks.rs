@@ -0,0 +1,39 @@
+use crate::last_known::BatteryReading;
+use std::process::Command;
+
+// Runs a user hook through the shell without waiting for it. The battery
+// state is passed in environment variables.
+pub fn run_hook(command: &str, reading: &BatteryReading, event: &str) -> Result<(), String> {
+    #[cfg(windows)]
+    let mut cmd = {
+        let mut cmd = Command::new("cmd");
+        cmd.args(["/C", command]);
+        cmd
+    };
+    #[cfg(not(windows))]
+    let mut cmd = {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", command]);
+        cmd
+    };
+
+    let mouse_name = reading
+        .status
+        .get_mouse_model()
+        .map(|m| m.name())
+        .unwrap_or("Mouse");
+    let percent = reading
+        .status
+        .percentage()
+        .or(reading.last_known.map(|last| last.percentage))
+        .map(|p| p.to_string())
+        .unwrap_or_default();
+
+    cmd.env("MOUSE_NAME", mouse_name)
+        .env("BATTERY_PERCENT", percent)
+        .env("BATTERY_STATE", reading.status.state_name())
+        .env("BATTERY_EVENT", event)
+        .spawn()
+        .map(|_| ())
+        .map_err(|e| format!("Failed to run hook \"{}\": {}", command, e))
+}
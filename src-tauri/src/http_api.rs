@@ -0,0 +1,533 @@
+use crate::last_known::BatteryReading;
+use crate::log_error;
+use crate::metrics;
+use crate::mouse_battery::{BatteryStatus, MouseInfo};
+use crate::settings::{ApiSettings, Settings};
+use serde::Serialize;
+use serde_json::json;
+use std::net::Ipv4Addr;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::{mpsc, watch};
+use tokio::task::JoinSet;
+use tokio::time::{interval, timeout};
+
+// Limits for reading a request; bodies are never read
+const MAX_HEAD: u64 = 8 * 1024;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+// Comment sent on idle event streams so dead clients are noticed
+const KEEP_ALIVE: Duration = Duration::from_secs(30);
+
+// Runs the API while api.enabled is set, starting over when the [api]
+// settings change, until the settings channel closes. Open connections are
+// dropped on a restart so a new token applies to them as well.
+pub async fn serve(
+    mut settings: watch::Receiver<Settings>,
+    readings: watch::Receiver<Vec<BatteryReading>>,
+    refresh: mpsc::Sender<()>,
+) {
+    loop {
+        let api = settings.borrow_and_update().api.clone();
+        let listener = if api.enabled {
+            match TcpListener::bind((Ipv4Addr::LOCALHOST, api.port)).await {
+                Ok(listener) => {
+                    log_error(&format!("HTTP API listening on 127.0.0.1:{}", api.port));
+                    Some(listener)
+                }
+                Err(e) => {
+                    log_error(&format!(
+                        "Failed to start the HTTP API on port {}: {}",
+                        api.port, e
+                    ));
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        tokio::select! {
+            _ = accept(listener, &api, &readings, &refresh) => {}
+            changed = api_changed(&mut settings, &api) => {
+                if !changed {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+// Waits until the [api] settings differ from `api`; false once the
+// settings channel is closed
+async fn api_changed(settings: &mut watch::Receiver<Settings>, api: &ApiSettings) -> bool {
+    loop {
+        if settings.changed().await.is_err() {
+            return false;
+        }
+        if settings.borrow_and_update().api != *api {
+            return true;
+        }
+    }
+}
+
+// Serves connections on the listener, or waits forever without one
+async fn accept(
+    listener: Option<TcpListener>,
+    api: &ApiSettings,
+    readings: &watch::Receiver<Vec<BatteryReading>>,
+    refresh: &mpsc::Sender<()>,
+) {
+    let Some(listener) = listener else {
+        return std::future::pending().await;
+    };
+    // Aborted together with this future
+    let mut connections = JoinSet::new();
+
+    loop {
+        match listener.accept().await {
+            Ok((stream, _)) => {
+                connections.spawn(handle(
+                    stream,
+                    api.clone(),
+                    readings.clone(),
+                    refresh.clone(),
+                ));
+            }
+            Err(e) => log_error(&format!("HTTP API failed to accept a connection: {}", e)),
+        }
+        while connections.try_join_next().is_some() {}
+    }
+}
+
+struct Request {
+    method: String,
+    path: String,
+    query: Option<String>,
+    host: Option<String>,
+    origin: Option<String>,
+    authorization: Option<String>,
+}
+
+async fn handle(
+    mut stream: TcpStream,
+    api: ApiSettings,
+    readings: watch::Receiver<Vec<BatteryReading>>,
+    refresh: mpsc::Sender<()>,
+) {
+    let request = match timeout(REQUEST_TIMEOUT, read_request(&mut stream)).await {
+        Ok(Some(request)) => request,
+        _ => {
+            let _ = respond(&mut stream, 400, &json!({ "error": "Bad request" })).await;
+            return;
+        }
+    };
+
+    // Browsers always send Host; anything but localhost is a page trying
+    // to reach the API through DNS rebinding
+    if !request.host.as_deref().is_none_or(is_local_host) {
+        let _ = respond(&mut stream, 403, &json!({ "error": "Forbidden host" })).await;
+        return;
+    }
+    // Browsers send Origin with cross-origin requests, including no-cors
+    // POSTs a page can make without reading the answer. The API has no
+    // use for them.
+    if request.origin.is_some() {
+        let _ = respond(&mut stream, 403, &json!({ "error": "Forbidden origin" })).await;
+        return;
+    }
+    if let Some(token) = &api.token {
+        if !authorized(&request, token) {
+            let _ = respond(
+                &mut stream,
+                401,
+                &json!({ "error": "Missing or wrong token" }),
+            )
+            .await;
+            return;
+        }
+    }
+
+    let path = request.path.trim_end_matches('/');
+    let current = devices(&readings.borrow());
+    let result = match (request.method.as_str(), path) {
+        ("GET", "/devices") => respond(&mut stream, 200, &current).await,
+        ("GET", "/events") => events(&mut stream, readings).await,
+        ("GET", "/metrics") if api.metrics => {
+            let body = metrics::render(&readings.borrow());
+            respond_with(&mut stream, 200, "text/plain; version=0.0.4", &body).await
+        }
+        // A refresh that is already pending covers this one as well
+        ("POST", "/refresh") => match refresh.try_send(()) {
+            Ok(()) | Err(TrySendError::Full(())) => respond(&mut stream, 202, &json!({})).await,
+            Err(TrySendError::Closed(())) => {
+                let error = json!({ "error": "Monitoring has stopped" });
+                respond(&mut stream, 503, &error).await
+            }
+        },
+        ("GET", _) if path.starts_with("/devices/") => {
+            let id = path["/devices/".len()..].trim_start_matches("0x");
+            match current
+                .iter()
+                .find(|device| device.id.eq_ignore_ascii_case(id))
+            {
+                Some(device) => respond(&mut stream, 200, &device).await,
+                None => respond(&mut stream, 404, &json!({ "error": "No such device" })).await,
+            }
+        }
+        (_, "/devices" | "/events" | "/refresh") => {
+            let error = json!({ "error": "Method not allowed" });
+            respond(&mut stream, 405, &error).await
+        }
+        _ => respond(&mut stream, 404, &json!({ "error": "Not found" })).await,
+    };
+    if let Err(e) = result {
+        log_error(&format!("HTTP API failed to respond: {}", e));
+    }
+}
+
+// Reads the request line and the headers we look at; None for anything
+// that isn't a well-formed HTTP/1 request
+async fn read_request(stream: &mut TcpStream) -> Option<Request> {
+    let mut reader = BufReader::new(stream.take(MAX_HEAD));
+    let mut line = String::new();
+    reader.read_line(&mut line).await.ok()?;
+
+    let mut parts = line.split_whitespace();
+    let method = parts.next()?.to_string();
+    let target = parts.next()?;
+    if !parts.next()?.starts_with("HTTP/1.") {
+        return None;
+    }
+    let (path, query) = match target.split_once('?') {
+        Some((path, query)) => (path.to_string(), Some(query.to_string())),
+        None => (target.to_string(), None),
+    };
+
+    let mut request = Request {
+        method,
+        path,
+        query,
+        host: None,
+        origin: None,
+        authorization: None,
+    };
+    loop {
+        line.clear();
+        if reader.read_line(&mut line).await.ok()? == 0 {
+            // Cut off by MAX_HEAD or the client
+            return None;
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            return Some(request);
+        }
+        let (name, value) = header.split_once(':')?;
+        let value = Some(value.trim().to_string());
+        if name.eq_ignore_ascii_case("host") {
+            request.host = value;
+        } else if name.eq_ignore_ascii_case("origin") {
+            request.origin = value;
+        } else if name.eq_ignore_ascii_case("authorization") {
+            request.authorization = value;
+        }
+    }
+}
+
+fn is_local_host(host: &str) -> bool {
+    let name = match host.rsplit_once(':') {
+        Some((name, port)) if port.chars().all(|c| c.is_ascii_digit()) => name,
+        _ => host,
+    };
+    matches!(name, "127.0.0.1" | "localhost" | "[::1]")
+}
+
+// Token as "Authorization: Bearer <token>", or ?token=<token> for clients
+// like EventSource that can't set headers
+fn authorized(request: &Request, token: &str) -> bool {
+    let bearer = request
+        .authorization
+        .as_deref()
+        .and_then(|value| value.strip_prefix("Bearer "));
+    let query = request.query.as_deref().and_then(|query| {
+        query
+            .split('&')
+            .find_map(|pair| pair.strip_prefix("token="))
+    });
+    [bearer, query]
+        .into_iter()
+        .flatten()
+        .any(|given| constant_time_eq(given.trim().as_bytes(), token.as_bytes()))
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+// A mouse as the API shows it: its MouseInfo plus what the tray shows
+#[derive(Serialize)]
+struct Device {
+    // Product ID in hex, e.g. 2034
+    id: String,
+    // Level shown in the tray, the last known one while asleep
+    level: Option<u8>,
+    state: &'static str,
+    stale: bool,
+    #[serde(flatten)]
+    info: MouseInfo,
+}
+
+fn devices(readings: &[BatteryReading]) -> Vec<Device> {
+    readings
+        .iter()
+        .filter(|reading| !matches!(reading.status, BatteryStatus::NotFound))
+        .filter_map(|reading| {
+            Some(Device {
+                id: reading.device_id()?,
+                level: reading.level(),
+                state: reading.status.state_name(),
+                stale: reading.is_stale(),
+                info: MouseInfo {
+                    battery_status: reading.status.clone(),
+                    raw_percentage: reading.raw_percentage,
+                    firmware_version: reading.firmware.clone(),
+                },
+            })
+        })
+        .collect()
+}
+
+async fn respond(
+    stream: &mut TcpStream,
+    status: u16,
+    body: &impl Serialize,
+) -> std::io::Result<()> {
+    let body = serde_json::to_string(body).map_err(std::io::Error::other)?;
+    respond_with(stream, status, "application/json", &body).await
+}
+
+async fn respond_with(
+    stream: &mut TcpStream,
+    status: u16,
+    content_type: &str,
+    body: &str,
+) -> std::io::Result<()> {
+    let mut response = format!("HTTP/1.1 {}\r\n", status_line(status));
+    if status == 401 {
+        response.push_str("WWW-Authenticate: Bearer\r\n");
+    }
+    response.push_str(&format!(
+        "Content-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        content_type,
+        body.len(),
+        body
+    ));
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+fn status_line(status: u16) -> &'static str {
+    match status {
+        200 => "200 OK",
+        202 => "202 Accepted",
+        400 => "400 Bad Request",
+        401 => "401 Unauthorized",
+        403 => "403 Forbidden",
+        404 => "404 Not Found",
+        405 => "405 Method Not Allowed",
+        _ => "503 Service Unavailable",
+    }
+}
+
+// Server-Sent Events: a "devices" event with the list of devices now and
+// whenever it changes, until the client goes away
+async fn events(
+    stream: &mut TcpStream,
+    mut readings: watch::Receiver<Vec<BatteryReading>>,
+) -> std::io::Result<()> {
+    stream
+        .write_all(
+            b"HTTP/1.1 200 OK\r\n\
+              Content-Type: text/event-stream\r\n\
+              Cache-Control: no-cache\r\n\
+              Connection: close\r\n\r\n",
+        )
+        .await?;
+
+    let mut keep_alive = interval(KEEP_ALIVE);
+    keep_alive.reset();
+    let mut last = None;
+    loop {
+        let data = serde_json::to_string(&devices(&readings.borrow_and_update()))
+            .map_err(std::io::Error::other)?;
+        if last.as_ref() != Some(&data) {
+            stream
+                .write_all(format!("event: devices\ndata: {}\n\n", data).as_bytes())
+                .await?;
+            last = Some(data);
+        }
+
+        loop {
+            tokio::select! {
+                changed = readings.changed() => {
+                    if changed.is_err() {
+                        return Ok(());
+                    }
+                    break;
+                }
+                _ = keep_alive.tick() => stream.write_all(b": keep-alive\n\n").await?,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mouse_battery::MouseModel;
+
+    // Sends `request` to a connection handled like the API's and returns
+    // the whole answer
+    async fn exchange(
+        request: &str,
+        api: ApiSettings,
+        readings: Vec<BatteryReading>,
+        refresh: &mpsc::Sender<()>,
+    ) -> String {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap())
+            .await
+            .unwrap();
+        let (stream, _) = listener.accept().await.unwrap();
+        let (_readings_tx, readings) = watch::channel(readings);
+        let server = tokio::spawn(handle(stream, api, readings, refresh.clone()));
+
+        client.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        client.read_to_string(&mut response).await.unwrap();
+        server.await.unwrap();
+        response
+    }
+
+    // Status line of the answer with the default settings and no mice
+    async fn send(request: &str, refresh: &mpsc::Sender<()>) -> String {
+        let response = exchange(request, ApiSettings::default(), Vec::new(), refresh).await;
+        response.lines().next().unwrap_or_default().to_string()
+    }
+
+    fn body(response: &str) -> serde_json::Value {
+        let (_, body) = response.split_once("\r\n\r\n").unwrap();
+        serde_json::from_str(body).unwrap()
+    }
+
+    #[tokio::test]
+    async fn refuses_cross_origin_requests() {
+        let (refresh_tx, mut refresh_rx) = mpsc::channel(1);
+        let request = "POST /refresh HTTP/1.1\r\nHost: 127.0.0.1\r\n\
+                       Origin: https://example.com\r\n\r\n";
+        assert_eq!(send(request, &refresh_tx).await, "HTTP/1.1 403 Forbidden");
+        assert!(refresh_rx.try_recv().is_err());
+
+        let request = "GET /devices HTTP/1.1\r\nHost: localhost\r\nOrigin: null\r\n\r\n";
+        assert_eq!(send(request, &refresh_tx).await, "HTTP/1.1 403 Forbidden");
+    }
+
+    #[tokio::test]
+    async fn merges_pending_refreshes() {
+        let (refresh_tx, mut refresh_rx) = mpsc::channel(1);
+        let request = "POST /refresh HTTP/1.1\r\nHost: localhost\r\n\r\n";
+        assert_eq!(send(request, &refresh_tx).await, "HTTP/1.1 202 Accepted");
+        assert_eq!(send(request, &refresh_tx).await, "HTTP/1.1 202 Accepted");
+        assert_eq!(refresh_rx.try_recv(), Ok(()));
+        assert!(refresh_rx.try_recv().is_err());
+
+        drop(refresh_rx);
+        assert_eq!(
+            send(request, &refresh_tx).await,
+            "HTTP/1.1 503 Service Unavailable"
+        );
+    }
+
+    #[tokio::test]
+    async fn requires_the_token_when_one_is_set() {
+        let (refresh_tx, _refresh_rx) = mpsc::channel(1);
+        let api = ApiSettings {
+            token: Some("secret".to_string()),
+            ..ApiSettings::default()
+        };
+        let cases = [
+            ("/devices", "", "HTTP/1.1 401 Unauthorized"),
+            (
+                "/devices",
+                "Authorization: Bearer wrong\r\n",
+                "HTTP/1.1 401 Unauthorized",
+            ),
+            (
+                "/devices",
+                "Authorization: secret\r\n",
+                "HTTP/1.1 401 Unauthorized",
+            ),
+            (
+                "/devices",
+                "Authorization: Bearer secret\r\n",
+                "HTTP/1.1 200 OK",
+            ),
+            ("/devices?token=secret", "", "HTTP/1.1 200 OK"),
+        ];
+
+        for (path, headers, expected) in cases {
+            let request = format!(
+                "GET {} HTTP/1.1\r\nHost: localhost\r\n{}\r\n",
+                path, headers
+            );
+            let response = exchange(&request, api.clone(), Vec::new(), &refresh_tx).await;
+            assert_eq!(response.lines().next(), Some(expected), "{:?}", headers);
+        }
+    }
+
+    #[tokio::test]
+    async fn looks_up_devices_by_id() {
+        let (refresh_tx, _refresh_rx) = mpsc::channel(1);
+        let readings = vec![BatteryReading {
+            status: BatteryStatus::Normal {
+                percentage: 60,
+                mouse_model: MouseModel::from_product_id(0x2034),
+            },
+            last_known: None,
+            remaining: None,
+            firmware: None,
+            raw_percentage: None,
+        }];
+        let get = |path: &str| format!("GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path);
+
+        for path in ["/devices/2034", "/devices/0x2034/"] {
+            let response = exchange(
+                &get(path),
+                ApiSettings::default(),
+                readings.clone(),
+                &refresh_tx,
+            )
+            .await;
+            assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+            let device = body(&response);
+            assert_eq!(device["id"], "2034");
+            assert_eq!(device["level"], 60);
+            assert_eq!(device["state"], "discharging");
+        }
+
+        let response = exchange(
+            &get("/devices/2011"),
+            ApiSettings::default(),
+            readings,
+            &refresh_tx,
+        )
+        .await;
+        assert!(
+            response.starts_with("HTTP/1.1 404 Not Found\r\n"),
+            "{}",
+            response
+        );
+        assert_eq!(body(&response)["error"], "No such device");
+    }
+}
@@ -0,0 +1,173 @@
+use fluent_bundle::concurrent::FluentBundle;
+use fluent_bundle::{FluentArgs, FluentResource, FluentValue};
+use std::collections::BTreeSet;
+use std::sync::RwLock;
+use unic_langid::LanguageIdentifier;
+
+// Complete catalogue, used for anything another locale doesn't translate
+pub const DEFAULT_LOCALE: &str = "en-US";
+
+// Bundled message catalogues in Fluent syntax
+const LOCALES: &[(&str, &str)] = &[
+    ("en-US", include_str!("../locales/en-US/main.ftl")),
+    ("de", include_str!("../locales/de/main.ftl")),
+];
+
+struct Catalog {
+    bundle: FluentBundle<FluentResource>,
+    fallback: FluentBundle<FluentResource>,
+}
+
+// Active catalogue, set up from the system locale on first use
+static CATALOG: RwLock<Option<Catalog>> = RwLock::new(None);
+
+fn source(locale: &str) -> &'static str {
+    LOCALES
+        .iter()
+        .find(|(name, _)| *name == locale)
+        .map(|(_, source)| *source)
+        .unwrap_or("")
+}
+
+fn build_bundle(locale: &'static str) -> FluentBundle<FluentResource> {
+    let langid: LanguageIdentifier = locale.parse().unwrap_or_default();
+    let mut bundle = FluentBundle::new_concurrent(vec![langid]);
+    // Direction marks around values show up as boxes in some tray tooltips
+    bundle.set_use_isolating(false);
+
+    // A broken catalogue still yields its valid messages
+    let resource = FluentResource::try_new(source(locale).to_string())
+        .unwrap_or_else(|(resource, _)| resource);
+    let _ = bundle.add_resource(resource);
+    bundle
+}
+
+// Bundled locale for a tag such as "de", "de-AT" or "de_DE.UTF-8", matching
+// the language when there's no exact match
+pub fn resolve(tag: &str) -> Option<&'static str> {
+    let tag = tag.split(['.', '@']).next()?.replace('_', "-");
+    let requested: LanguageIdentifier = tag.parse().ok()?;
+
+    LOCALES
+        .iter()
+        .map(|(name, _)| *name)
+        .find(|name| name.parse::<LanguageIdentifier>().ok().as_ref() == Some(&requested))
+        .or_else(|| {
+            LOCALES.iter().map(|(name, _)| *name).find(|name| {
+                name.parse::<LanguageIdentifier>()
+                    .is_ok_and(|langid| langid.language == requested.language)
+            })
+        })
+}
+
+pub fn available() -> Vec<&'static str> {
+    LOCALES.iter().map(|(name, _)| *name).collect()
+}
+
+fn system_locale() -> &'static str {
+    sys_locale::get_locale()
+        .and_then(|tag| resolve(&tag))
+        .unwrap_or(DEFAULT_LOCALE)
+}
+
+// Switches the catalogue for the `language` setting: "auto" follows the system
+pub fn set_language(language: &str) {
+    let locale = if language == "auto" {
+        system_locale()
+    } else {
+        resolve(language).unwrap_or(DEFAULT_LOCALE)
+    };
+    *CATALOG.write().unwrap() = Some(Catalog {
+        bundle: build_bundle(locale),
+        fallback: build_bundle(DEFAULT_LOCALE),
+    });
+}
+
+pub fn tr(id: &str) -> String {
+    tr_with(id, &[])
+}
+
+// Message with arguments, e.g. tr_with("status-normal", &[("name", name.into())])
+pub fn tr_with(id: &str, args: &[(&str, FluentValue)]) -> String {
+    if CATALOG.read().unwrap().is_none() {
+        set_language("auto");
+    }
+
+    let mut fluent_args = FluentArgs::new();
+    for (name, value) in args {
+        fluent_args.set(*name, value.clone());
+    }
+
+    let catalog = CATALOG.read().unwrap();
+    let Some(catalog) = catalog.as_ref() else {
+        return id.to_string();
+    };
+    format(&catalog.bundle, id, &fluent_args)
+        .or_else(|| format(&catalog.fallback, id, &fluent_args))
+        .unwrap_or_else(|| id.to_string())
+}
+
+fn format(bundle: &FluentBundle<FluentResource>, id: &str, args: &FluentArgs) -> Option<String> {
+    let pattern = bundle.get_message(id)?.value()?;
+    let mut errors = Vec::new();
+    Some(
+        bundle
+            .format_pattern(pattern, Some(args), &mut errors)
+            .into_owned(),
+    )
+}
+
+fn message_ids(source: &str) -> Result<BTreeSet<String>, String> {
+    use fluent_syntax::ast::Entry;
+
+    let resource = FluentResource::try_new(source.to_string())
+        .map_err(|(_, errors)| format!("{} syntax error(s)", errors.len()))?;
+    Ok(resource
+        .entries()
+        .filter_map(|entry| match entry {
+            Entry::Message(message) => Some(message.id.name.to_string()),
+            _ => None,
+        })
+        .collect())
+}
+
+// Problems in the bundled catalogues: syntax errors, and messages missing
+// from or unknown to the default locale
+pub fn check_catalogs() -> Vec<String> {
+    let mut problems = Vec::new();
+    let expected = match message_ids(source(DEFAULT_LOCALE)) {
+        Ok(ids) => ids,
+        Err(e) => return vec![format!("{}: {}", DEFAULT_LOCALE, e)],
+    };
+
+    for (locale, source) in LOCALES.iter().filter(|(name, _)| *name != DEFAULT_LOCALE) {
+        let ids = match message_ids(source) {
+            Ok(ids) => ids,
+            Err(e) => {
+                problems.push(format!("{}: {}", locale, e));
+                continue;
+            }
+        };
+        for id in expected.difference(&ids) {
+            problems.push(format!("{}: missing message {}", locale, id));
+        }
+        for id in ids.difference(&expected) {
+            problems.push(format!(
+                "{}: message {} is not in {}",
+                locale, id, DEFAULT_LOCALE
+            ));
+        }
+    }
+
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bundled_catalogs_match() {
+        assert_eq!(check_catalogs(), Vec::<String>::new());
+    }
+}
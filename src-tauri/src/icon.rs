@@ -0,0 +1,878 @@
+use crate::icon_pack::IconPack;
+use crate::last_known::BatteryReading;
+use crate::mouse_battery::{BatteryStatus, MouseModel};
+use crate::settings::IconSettings;
+use crate::template;
+use crate::theme::Theme;
+use ab_glyph::{point, Font, FontRef, Glyph, PxScale, ScaleFont};
+use image::{imageops, ImageBuffer, Rgba, RgbaImage};
+use imageproc::drawing::{draw_filled_rect_mut, draw_polygon_mut};
+use imageproc::point::Point;
+use imageproc::rect::Rect;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+// Sizes trays commonly ask for, in physical pixels
+pub const ICON_SIZES: &[u32] = &[16, 22, 24, 32, 48];
+
+// Rendered icons kept before the cache starts over
+const CACHE_LIMIT: usize = 256;
+
+// Frames in one cycle of the charging animation
+pub const CHARGING_FRAMES: u8 = 4;
+
+const WHITE: Rgba<u8> = Rgba([255, 255, 255, 255]);
+const BLACK: Rgba<u8> = Rgba([0, 0, 0, 255]);
+const DARK_GREY: Rgba<u8> = Rgba([32, 32, 32, 255]);
+const GREEN: Rgba<u8> = Rgba([76, 209, 55, 255]);
+const AMBER: Rgba<u8> = Rgba([255, 176, 32, 255]);
+const RED: Rgba<u8> = Rgba([235, 59, 59, 255]);
+const TRANSPARENT: Rgba<u8> = Rgba([0, 0, 0, 0]);
+
+// How the tray icon presents the battery level
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IconStyle {
+    // The percentage as large digits
+    Text,
+    // A battery gauge filled to the level
+    Gauge,
+    // The bundled battery_*.png images
+    Png,
+    // A gauge with the percentage on top
+    GaugeText,
+}
+
+// Picks the icon size in physical pixels. A configured size of 0 means
+// automatic: the platform's logical tray size scaled for the display,
+// snapped to the nearest common size.
+pub fn tray_icon_size(configured: u32, scale_factor: f64) -> u32 {
+    if configured != 0 {
+        return configured;
+    }
+
+    let logical = if cfg!(windows) { 16.0 } else { 22.0 };
+    let wanted = (logical * scale_factor).round() as u32;
+    ICON_SIZES
+        .iter()
+        .copied()
+        .min_by_key(|size| size.abs_diff(wanted))
+        .unwrap_or(32)
+}
+
+// Foreground and outline colours that stand out on the panel
+struct Palette {
+    foreground: Rgba<u8>,
+    outline: Rgba<u8>,
+}
+
+impl Palette {
+    fn for_theme(theme: Theme) -> Self {
+        match theme {
+            Theme::Dark => Palette {
+                foreground: WHITE,
+                outline: BLACK,
+            },
+            Theme::Light => Palette {
+                foreground: DARK_GREY,
+                outline: WHITE,
+            },
+        }
+    }
+}
+
+// Animation frame an icon is drawn for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Frame {
+    Still,
+    // Step of the charging fill sweep, 0 to CHARGING_FRAMES - 1
+    Charging(u8),
+    // Dimmed half of the low-battery blink
+    BlinkOff,
+}
+
+// Frame to show for a reading on animation tick `tick`. Charging sweeps the
+// fill; at or below `critical_threshold` the icon blinks if enabled.
+pub fn animation_frame(
+    reading: &BatteryReading,
+    settings: &IconSettings,
+    critical_threshold: u8,
+    tick: u64,
+) -> Frame {
+    if !settings.animate || reading.is_stale() {
+        return Frame::Still;
+    }
+
+    match reading.status {
+        BatteryStatus::Charging { .. } => Frame::Charging((tick % CHARGING_FRAMES as u64) as u8),
+        BatteryStatus::Normal { percentage, .. }
+            if settings.blink_critical && percentage <= critical_threshold && tick % 2 == 1 =>
+        {
+            Frame::BlinkOff
+        }
+        _ => Frame::Still,
+    }
+}
+
+// Whether the icon for a reading changes with the animation tick. Tick 1 is
+// a moving frame for both the charging sweep and the blink.
+pub fn is_animated(
+    reading: &BatteryReading,
+    settings: &IconSettings,
+    critical_threshold: u8,
+) -> bool {
+    animation_frame(reading, settings, critical_threshold, 1) != Frame::Still
+}
+
+// Renders the tray icon for a reading at size x size pixels, from the icon
+// pack when it has an image for the reading, otherwise in the configured
+// style coloured for a panel with the given theme
+pub fn render_icon(
+    reading: &BatteryReading,
+    settings: &IconSettings,
+    size: u32,
+    theme: Theme,
+    pack: Option<&IconPack>,
+    frame: Frame,
+) -> Result<RgbaImage, String> {
+    let state = reading.status.state_name();
+    let pack_icon = pack.and_then(|pack| pack.render(state, reading.level(), size));
+
+    let palette = Palette::for_theme(theme);
+    let mut img = match pack_icon {
+        Some(mut img) => {
+            if reading.is_stale() {
+                fade(&mut img, 120);
+            }
+            img
+        }
+        None => match settings.style {
+            IconStyle::Text => create_text_icon(reading, settings, size, &palette, frame)?,
+            IconStyle::Gauge => create_gauge_icon(reading, settings, size, &palette, frame, false)?,
+            IconStyle::GaugeText => {
+                create_gauge_icon(reading, settings, size, &palette, frame, true)?
+            }
+            IconStyle::Png => load_png_icon(reading, size)?,
+        },
+    };
+
+    if let Some(model) = reading.status.get_mouse_model() {
+        draw_badges(&mut img, model, settings, &palette)?;
+    }
+
+    if frame == Frame::BlinkOff {
+        fade(&mut img, 60);
+    }
+    Ok(img)
+}
+
+// Scales the alpha of every pixel by alpha / 255
+fn fade(img: &mut RgbaImage, alpha: u8) {
+    for pixel in img.pixels_mut() {
+        pixel[3] = (pixel[3] as u16 * alpha as u16 / 255) as u8;
+    }
+}
+
+// Everything about a reading that affects how its icon looks
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct IconKey {
+    style: IconStyle,
+    state: &'static str,
+    theme: Theme,
+    size: u32,
+    text: String,
+    level: Option<u8>,
+    charging: bool,
+    stale: bool,
+    frame: Frame,
+    model: Option<MouseModel>,
+}
+
+impl IconKey {
+    fn new(
+        reading: &BatteryReading,
+        settings: &IconSettings,
+        size: u32,
+        theme: Theme,
+        frame: Frame,
+    ) -> Self {
+        IconKey {
+            style: settings.style,
+            state: reading.status.state_name(),
+            theme,
+            size,
+            text: icon_text(reading, settings),
+            level: reading.level(),
+            charging: is_charging(reading),
+            stale: reading.is_stale(),
+            frame,
+            model: reading.status.get_mouse_model(),
+        }
+    }
+}
+
+// Rendered icons by what they show, so a level that comes back is not
+// drawn again. Cleared when the icon settings or the icon pack change.
+pub struct IconCache {
+    settings: Option<IconSettings>,
+    icons: HashMap<IconKey, RgbaImage>,
+    last: Option<IconKey>,
+}
+
+impl IconCache {
+    pub fn new() -> Self {
+        IconCache {
+            settings: None,
+            icons: HashMap::new(),
+            last: None,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.icons.clear();
+        self.last = None;
+    }
+
+    // Returns the icon for a reading, rendering it on a cache miss, and
+    // whether it differs from the icon returned last time
+    pub fn get(
+        &mut self,
+        reading: &BatteryReading,
+        settings: &IconSettings,
+        size: u32,
+        theme: Theme,
+        pack: Option<&IconPack>,
+        frame: Frame,
+    ) -> Result<(RgbaImage, bool), String> {
+        if self.settings.as_ref() != Some(settings) {
+            self.settings = Some(settings.clone());
+            self.clear();
+        }
+
+        let frame = drawn_frame(frame, reading, settings, pack);
+        let key = IconKey::new(reading, settings, size, theme, frame);
+        let icon = match self.icons.get(&key) {
+            Some(icon) => icon.clone(),
+            None => {
+                let icon = render_icon(reading, settings, size, theme, pack, frame)?;
+                if self.icons.len() >= CACHE_LIMIT {
+                    self.icons.clear();
+                }
+                self.icons.insert(key.clone(), icon.clone());
+                icon
+            }
+        };
+
+        let changed = self.last.as_ref() != Some(&key);
+        self.last = Some(key);
+        Ok((icon, changed))
+    }
+}
+
+// Charging frames only differ for the gauge's sweep and the text style's
+// pulsing bolt. Anything else is drawn still, so every frame hits the same
+// cache entry and the tray icon isn't replaced twice a second.
+fn drawn_frame(
+    frame: Frame,
+    reading: &BatteryReading,
+    settings: &IconSettings,
+    pack: Option<&IconPack>,
+) -> Frame {
+    let Frame::Charging(step) = frame else {
+        return frame;
+    };
+    if pack.is_some_and(|pack| pack.covers(reading.status.state_name(), reading.level())) {
+        return Frame::Still;
+    }
+    match settings.style {
+        IconStyle::Gauge | IconStyle::GaugeText => frame,
+        // The bolt is either shown or not
+        IconStyle::Text if settings.charging_bolt => {
+            Frame::Charging(step / (CHARGING_FRAMES / 2) * (CHARGING_FRAMES / 2))
+        }
+        IconStyle::Text | IconStyle::Png => Frame::Still,
+    }
+}
+
+fn is_charging(reading: &BatteryReading) -> bool {
+    matches!(
+        reading.status,
+        BatteryStatus::Charging { .. } | BatteryStatus::FullyCharged { .. }
+    )
+}
+
+pub fn level_color(level: u8, settings: &IconSettings) -> Rgba<u8> {
+    if level <= settings.red_threshold {
+        RED
+    } else if level <= settings.amber_threshold {
+        AMBER
+    } else {
+        GREEN
+    }
+}
+
+// Dims a colour when the icon shows a cached level
+fn with_staleness(color: Rgba<u8>, reading: &BatteryReading) -> Rgba<u8> {
+    if reading.is_stale() {
+        Rgba([color[0], color[1], color[2], 120])
+    } else {
+        color
+    }
+}
+
+pub fn icon_text(reading: &BatteryReading, settings: &IconSettings) -> String {
+    // The template covers readings with a level; the state texts the rest
+    if let (Some(text), Some(_)) = (&settings.text, reading.level()) {
+        return template::render(text, reading);
+    }
+
+    match (&reading.status, reading.last_known) {
+        // Show the cached level when the mouse can't report one
+        (_, Some(last)) => format!("{}", last.percentage),
+        (BatteryStatus::Normal { percentage, .. }, _) => format!("{}", percentage),
+        (BatteryStatus::Charging { percentage, .. }, _) => format!("{}", percentage),
+        (BatteryStatus::FullyCharged { .. }, _) => "100".to_string(),
+        (BatteryStatus::Asleep { .. }, _) => settings.asleep_text.clone(),
+        (BatteryStatus::WakingUp { .. }, _) => settings.waking_text.clone(),
+        (BatteryStatus::NotFound, _) => settings.not_found_text.clone(),
+        (BatteryStatus::Unknown { .. }, _) => settings.unknown_text.clone(),
+    }
+}
+
+// The bundled font, parsed on first use
+fn font() -> Result<&'static FontRef<'static>, String> {
+    static FONT: OnceLock<Option<FontRef<'static>>> = OnceLock::new();
+    FONT.get_or_init(|| {
+        let font_data = include_bytes!("../assets/DejaVuSans.ttf");
+        FontRef::try_from_slice(font_data).ok()
+    })
+    .as_ref()
+    .ok_or_else(|| "Failed to load font".to_string())
+}
+
+// Positions the glyphs of a single line on a baseline at y = 0, with kerning
+fn layout_glyphs(font: &FontRef<'_>, scale: PxScale, text: &str) -> Vec<Glyph> {
+    let scaled = font.as_scaled(scale);
+    let mut caret = 0.0;
+    let mut previous = None;
+    let mut glyphs = Vec::new();
+
+    for c in text.chars() {
+        let id = font.glyph_id(c);
+        if let Some(previous) = previous {
+            caret += scaled.kern(previous, id);
+        }
+        glyphs.push(id.with_scale_and_position(scale, point(caret, 0.0)));
+        caret += scaled.h_advance(id);
+        previous = Some(id);
+    }
+
+    glyphs
+}
+
+// Bounding box of the inked pixels as (min_x, min_y, max_x, max_y)
+fn ink_bounds(font: &FontRef<'_>, glyphs: &[Glyph]) -> Option<(f32, f32, f32, f32)> {
+    glyphs
+        .iter()
+        .filter_map(|glyph| font.outline_glyph(glyph.clone()))
+        .map(|outlined| outlined.px_bounds())
+        .map(|b| (b.min.x, b.min.y, b.max.x, b.max.y))
+        .reduce(|a, b| (a.0.min(b.0), a.1.min(b.1), a.2.max(b.2), a.3.max(b.3)))
+}
+
+// Largest scale at which the text's ink fits within max_w x max_h
+fn fit_scale(font: &FontRef<'_>, text: &str, max_w: f32, max_h: f32) -> PxScale {
+    const REFERENCE: f32 = 100.0;
+    let (w, h) = match ink_bounds(font, &layout_glyphs(font, PxScale::from(REFERENCE), text)) {
+        Some((x0, y0, x1, y1)) => (x1 - x0, y1 - y0),
+        None => return PxScale::from(max_h),
+    };
+
+    // Ink grows almost linearly with scale; step down until pixel
+    // rounding fits too
+    let mut size = REFERENCE * (max_w / w).min(max_h / h);
+    while size > 1.0 {
+        let fits = match ink_bounds(font, &layout_glyphs(font, PxScale::from(size), text)) {
+            Some((x0, y0, x1, y1)) => x1 - x0 <= max_w && y1 - y0 <= max_h,
+            None => true,
+        };
+        if fits {
+            break;
+        }
+        size *= 0.97;
+    }
+
+    PxScale::from(size)
+}
+
+// Composites `color` over the pixel at (x, y) with the given coverage
+fn blend_pixel(img: &mut RgbaImage, x: i32, y: i32, color: Rgba<u8>, coverage: f32) {
+    if x < 0 || y < 0 || x >= img.width() as i32 || y >= img.height() as i32 {
+        return;
+    }
+
+    let dst = img.get_pixel_mut(x as u32, y as u32);
+    let src_a = coverage.clamp(0.0, 1.0) * color[3] as f32 / 255.0;
+    let dst_a = dst[3] as f32 / 255.0;
+    let out_a = src_a + dst_a * (1.0 - src_a);
+    if out_a <= 0.0 {
+        return;
+    }
+
+    for i in 0..3 {
+        let value = (color[i] as f32 * src_a + dst[i] as f32 * dst_a * (1.0 - src_a)) / out_a;
+        dst[i] = value.round() as u8;
+    }
+    dst[3] = (out_a * 255.0).round() as u8;
+}
+
+// Draws text as large as fits in the box (x, y, w, h), centred on its ink
+// rather than its advance width, with an optional outline of the given width
+fn draw_text_fitted(
+    img: &mut RgbaImage,
+    font: &FontRef<'_>,
+    text: &str,
+    (x, y, w, h): (f32, f32, f32, f32),
+    color: Rgba<u8>,
+    outline: Option<(Rgba<u8>, i32)>,
+) {
+    // Single characters are sized like two so a lone digit doesn't balloon
+    let sizing_text = if text.chars().count() < 2 { "88" } else { text };
+    let pad = outline.map_or(0.0, |(_, width)| 2.0 * width as f32);
+    let scale = fit_scale(font, sizing_text, w - pad, h - pad);
+    let glyphs = layout_glyphs(font, scale, text);
+    let Some((x0, y0, x1, y1)) = ink_bounds(font, &glyphs) else {
+        return;
+    };
+
+    // Whole-pixel offset so vertical strokes stay crisp at small sizes
+    let dx = (x + (w - (x1 - x0)) / 2.0 - x0).round() as i32;
+    let dy = (y + (h - (y1 - y0)) / 2.0 - y0).round() as i32;
+
+    let mut passes = Vec::new();
+    if let Some((outline_color, width)) = outline {
+        for ox in -width..=width {
+            for oy in -width..=width {
+                if (ox, oy) != (0, 0) {
+                    passes.push((outline_color, ox, oy));
+                }
+            }
+        }
+    }
+    passes.push((color, 0, 0));
+
+    for (pass_color, ox, oy) in passes {
+        for glyph in &glyphs {
+            if let Some(outlined) = font.outline_glyph(glyph.clone()) {
+                let bounds = outlined.px_bounds();
+                let left = bounds.min.x as i32 + dx + ox;
+                let top = bounds.min.y as i32 + dy + oy;
+                outlined.draw(|gx, gy, coverage| {
+                    blend_pixel(img, left + gx as i32, top + gy as i32, pass_color, coverage);
+                });
+            }
+        }
+    }
+}
+
+fn create_text_icon(
+    reading: &BatteryReading,
+    settings: &IconSettings,
+    size: u32,
+    palette: &Palette,
+    frame: Frame,
+) -> Result<RgbaImage, String> {
+    let text = icon_text(reading, settings);
+    let mut img: RgbaImage = ImageBuffer::from_pixel(size, size, TRANSPARENT);
+    let font = font()?;
+    let s = size as f32;
+
+    // Theme foreground by default, or the level colour when enabled
+    let color = match reading.level() {
+        Some(level) if settings.colored_text => level_color(level, settings),
+        _ => palette.foreground,
+    };
+    let color = with_staleness(color, reading);
+
+    // The digits get the whole icon; the font is sized to fit whatever the text is
+    draw_text_fitted(&mut img, font, &text, (0.0, 0.0, s, s), color, None);
+
+    // The bolt pulses while charging is animated
+    let bolt_visible = !matches!(frame, Frame::Charging(step) if step >= CHARGING_FRAMES / 2);
+    if is_charging(reading) && settings.charging_bolt && bolt_visible {
+        let bolt = (s * 0.69, s * 0.03, s * 0.28, s * 0.44);
+        draw_bolt(
+            &mut img,
+            bolt,
+            with_staleness(AMBER, reading),
+            palette.outline,
+        );
+    }
+
+    Ok(img)
+}
+
+fn create_gauge_icon(
+    reading: &BatteryReading,
+    settings: &IconSettings,
+    size: u32,
+    palette: &Palette,
+    frame: Frame,
+    with_text: bool,
+) -> Result<RgbaImage, String> {
+    let mut img: RgbaImage = ImageBuffer::from_pixel(size, size, TRANSPARENT);
+    let level = reading.level();
+    let outline = with_staleness(palette.foreground, reading);
+    let s = size as f32;
+    let px = |fraction: f32| (s * fraction).round() as i32;
+
+    // Battery body with a terminal nub on the right, kept at least a pixel
+    // thick so it survives 16px icons
+    let border = px(0.055).max(1);
+    let gap = px(0.03);
+    let (body_x, body_y, body_w, body_h) = (px(0.03), px(0.22), px(0.845), px(0.56));
+    let (inner_x, inner_y) = (body_x + border, body_y + border);
+    let (inner_w, inner_h) = (body_w - 2 * border, body_h - 2 * border);
+    draw_filled_rect_mut(
+        &mut img,
+        Rect::at(body_x, body_y).of_size(body_w as u32, body_h as u32),
+        outline,
+    );
+    draw_filled_rect_mut(
+        &mut img,
+        Rect::at(inner_x, inner_y).of_size(inner_w as u32, inner_h as u32),
+        TRANSPARENT,
+    );
+    draw_filled_rect_mut(
+        &mut img,
+        Rect::at(body_x + body_w, px(0.39)).of_size(px(0.09).max(1) as u32, px(0.22) as u32),
+        outline,
+    );
+
+    // Fill proportional to the level, leaving a small gap to the border
+    if let Some(level) = level {
+        let level = level.min(100) as i32;
+        let fill_max = inner_w - 2 * gap;
+        let fill_h = (inner_h - 2 * gap) as u32;
+        let fill_w = (fill_max * level / 100).max(1);
+        let color = with_staleness(level_color(level as u8, settings), reading);
+        draw_filled_rect_mut(
+            &mut img,
+            Rect::at(inner_x + gap, inner_y + gap).of_size(fill_w as u32, fill_h),
+            color,
+        );
+
+        // While charging, a translucent band sweeps from the level to full
+        if let Frame::Charging(step) = frame {
+            let sweep = (100 - level) * step as i32 / (CHARGING_FRAMES as i32 - 1);
+            let sweep_w = fill_max * (level + sweep) / 100 - fill_w;
+            if sweep_w > 0 {
+                draw_filled_rect_mut(
+                    &mut img,
+                    Rect::at(inner_x + gap + fill_w, inner_y + gap).of_size(sweep_w as u32, fill_h),
+                    Rgba([color[0], color[1], color[2], 140]),
+                );
+            }
+        }
+    }
+
+    if with_text {
+        let text = icon_text(reading, settings);
+        let font = font()?;
+        let color = with_staleness(palette.foreground, reading);
+
+        // Contrasting outline so the digits stay readable over any fill
+        // colour; below 24px there is no room for one
+        let outline = (size >= 24).then_some((palette.outline, (size as i32 / 48).max(1)));
+        let area = (inner_x as f32, body_y as f32, inner_w as f32, body_h as f32);
+        draw_text_fitted(&mut img, font, &text, area, color, outline);
+    }
+
+    if is_charging(reading) && settings.charging_bolt {
+        // Keep the bolt clear of the digits when both are shown
+        let color = with_staleness(AMBER, reading);
+        if with_text {
+            draw_bolt(
+                &mut img,
+                (s * 0.77, 0.0, s * 0.22, s * 0.38),
+                color,
+                palette.outline,
+            );
+        } else {
+            draw_bolt(
+                &mut img,
+                (s * 0.33, s * 0.16, s * 0.34, s * 0.68),
+                color,
+                palette.outline,
+            );
+        }
+    }
+
+    Ok(img)
+}
+
+// Small overlays in the bottom corners: how the mouse is connected on the
+// left and which model it is on the right
+fn draw_badges(
+    img: &mut RgbaImage,
+    model: MouseModel,
+    settings: &IconSettings,
+    palette: &Palette,
+) -> Result<(), String> {
+    let s = img.width() as f32;
+    let badge = s * 0.44;
+    let y = s - badge;
+
+    if settings.connection_badge {
+        let area = (0.0, y, badge, badge);
+        draw_disc(img, area, palette.outline);
+        if model.is_wired() {
+            draw_plug(img, area, palette.foreground);
+        } else {
+            draw_wireless(img, area, palette.foreground);
+        }
+    }
+
+    if let Some(letter) = model.badge().filter(|_| settings.model_badge) {
+        let x = s - badge;
+        let inset = badge * 0.22;
+        let area = (
+            x + inset,
+            y + inset,
+            badge - 2.0 * inset,
+            badge - 2.0 * inset,
+        );
+        draw_disc(img, (x, y, badge, badge), palette.foreground);
+        draw_text_fitted(
+            img,
+            font()?,
+            &letter.to_string(),
+            area,
+            palette.outline,
+            None,
+        );
+    }
+
+    Ok(())
+}
+
+// Fills the circle inscribed in the box, with anti-aliased edges
+fn draw_disc(img: &mut RgbaImage, (x, y, w, h): (f32, f32, f32, f32), color: Rgba<u8>) {
+    let (cx, cy, r) = (x + w / 2.0, y + h / 2.0, w.min(h) / 2.0);
+    for py in y.floor() as i32..(y + h).ceil() as i32 {
+        for px in x.floor() as i32..(x + w).ceil() as i32 {
+            let d = ((px as f32 + 0.5 - cx).powi(2) + (py as f32 + 0.5 - cy).powi(2)).sqrt();
+            blend_pixel(img, px, py, color, r + 0.5 - d);
+        }
+    }
+}
+
+// A two-pronged plug with its cable, inside the box
+fn draw_plug(img: &mut RgbaImage, (x, y, w, h): (f32, f32, f32, f32), color: Rgba<u8>) {
+    let parts = [
+        (0.28, 0.40, 0.44, 0.26),
+        (0.34, 0.20, 0.09, 0.20),
+        (0.57, 0.20, 0.09, 0.20),
+        (0.45, 0.66, 0.10, 0.16),
+    ];
+    for (px, py, pw, ph) in parts {
+        let rect = Rect::at((x + px * w).round() as i32, (y + py * h).round() as i32).of_size(
+            ((pw * w).round() as u32).max(1),
+            ((ph * h).round() as u32).max(1),
+        );
+        draw_filled_rect_mut(img, rect, color);
+    }
+}
+
+// A dot with two arcs above it, inside the box
+fn draw_wireless(img: &mut RgbaImage, (x, y, w, h): (f32, f32, f32, f32), color: Rgba<u8>) {
+    let (cx, cy) = (x + w * 0.5, y + h * 0.72);
+    let thickness = (w * 0.1).max(1.0);
+    let rings = [
+        (0.0, w * 0.08),
+        (w * 0.24, thickness),
+        (w * 0.42, thickness),
+    ];
+
+    for py in y.floor() as i32..(y + h).ceil() as i32 {
+        for px in x.floor() as i32..(x + w).ceil() as i32 {
+            let (dx, dy) = (px as f32 + 0.5 - cx, py as f32 + 0.5 - cy);
+            let d = (dx * dx + dy * dy).sqrt();
+
+            // Only the upper quarter of each ring, plus the centre dot
+            let in_wedge = dy < 0.0 && dx.abs() <= -dy;
+            let coverage = rings
+                .iter()
+                .filter(|(radius, _)| *radius == 0.0 || in_wedge)
+                .map(|(radius, width)| width / 2.0 + 0.5 - (d - radius).abs())
+                .fold(0.0, f32::max);
+            blend_pixel(img, px, py, color, coverage);
+        }
+    }
+}
+
+// Draws a lightning bolt inside the box (x, y, w, h)
+fn draw_bolt(
+    img: &mut RgbaImage,
+    (x, y, w, h): (f32, f32, f32, f32),
+    color: Rgba<u8>,
+    border: Rgba<u8>,
+) {
+    const BOLT: [(f32, f32); 7] = [
+        (0.60, 0.0),
+        (0.10, 0.58),
+        (0.45, 0.58),
+        (0.30, 1.0),
+        (0.90, 0.38),
+        (0.55, 0.38),
+        (0.75, 0.0),
+    ];
+
+    // Points as fractions of the box, grown by `grow` around its centre
+    let polygon = |grow: f32| -> Vec<Point<i32>> {
+        let mut points: Vec<Point<i32>> = BOLT
+            .iter()
+            .map(|(px, py)| {
+                let px = 0.5 + (px - 0.5) * (1.0 + grow);
+                let py = 0.5 + (py - 0.5) * (1.0 + grow);
+                Point::new((x + px * w).round() as i32, (y + py * h).round() as i32)
+            })
+            .collect();
+
+        // Rounding can merge points at small sizes, and draw_polygon_mut
+        // panics on a closed path
+        points.dedup();
+        if points.len() > 1 && points.first() == points.last() {
+            points.pop();
+        }
+        points
+    };
+
+    // Border first so the bolt stands out over the digits
+    draw_polygon_mut(img, &polygon(0.16), border);
+    draw_polygon_mut(img, &polygon(0.0), color);
+}
+
+// The bundled battery art, drawn at 512 px and scaled down to the tray's
+// size. A cached level picks the image for that level, dimmed like the
+// other styles.
+fn load_png_icon(reading: &BatteryReading, size: u32) -> Result<RgbaImage, String> {
+    let status = match (reading.last_known, reading.status.get_mouse_model()) {
+        (Some(last), Some(mouse_model)) if last.charging => BatteryStatus::Charging {
+            percentage: last.percentage,
+            mouse_model,
+        },
+        (Some(last), Some(mouse_model)) => BatteryStatus::Normal {
+            percentage: last.percentage,
+            mouse_model,
+        },
+        _ => reading.status.clone(),
+    };
+    let bytes: &[u8] = match status.get_icon_name() {
+        "battery_0" => include_bytes!("../battery/battery_0.png"),
+        "battery_25" => include_bytes!("../battery/battery_25.png"),
+        "battery_50" => include_bytes!("../battery/battery_50.png"),
+        "battery_75" => include_bytes!("../battery/battery_75.png"),
+        "battery_100" => include_bytes!("../battery/battery_100.png"),
+        "battery_charging" => include_bytes!("../battery/battery_charging.png"),
+        _ => include_bytes!("../battery/battery_unknown.png"),
+    };
+
+    let img = image::load_from_memory(bytes)
+        .map_err(|e| format!("Failed to decode icon: {}", e))?
+        .to_rgba8();
+    let mut img = imageops::resize(&img, size, size, imageops::FilterType::Lanczos3);
+    if reading.is_stale() {
+        fade(&mut img, 120);
+    }
+    Ok(img)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn charging(percentage: u8) -> BatteryReading {
+        BatteryReading {
+            status: BatteryStatus::Charging {
+                percentage,
+                mouse_model: MouseModel::from_product_id(0x2034),
+            },
+            last_known: None,
+            remaining: None,
+            firmware: None,
+            raw_percentage: None,
+        }
+    }
+
+    // Whether each charging frame after the first replaces the tray icon
+    fn frames_change_icon(settings: &IconSettings) -> Vec<bool> {
+        let mut cache = IconCache::new();
+        let reading = charging(40);
+        (0..CHARGING_FRAMES)
+            .map(|step| {
+                let frame = Frame::Charging(step);
+                let (_, changed) = cache
+                    .get(&reading, settings, 32, Theme::Dark, None, frame)
+                    .unwrap();
+                changed
+            })
+            .skip(1)
+            .collect()
+    }
+
+    #[test]
+    fn animates_only_charging_and_critical_levels() {
+        let critical = BatteryReading {
+            status: BatteryStatus::Normal {
+                percentage: 5,
+                mouse_model: MouseModel::from_product_id(0x2034),
+            },
+            ..charging(5)
+        };
+        let settings = IconSettings {
+            animate: true,
+            blink_critical: true,
+            ..IconSettings::default()
+        };
+        assert!(is_animated(&charging(40), &settings, 10));
+        assert!(is_animated(&critical, &settings, 10));
+        assert!(!is_animated(&critical, &settings, 4));
+
+        let still = IconSettings {
+            animate: false,
+            ..settings
+        };
+        assert!(!is_animated(&charging(40), &still, 10));
+        assert!(!is_animated(&critical, &still, 10));
+    }
+
+    #[test]
+    fn still_styles_keep_the_icon_while_charging() {
+        for style in [IconStyle::Png, IconStyle::Text] {
+            let settings = IconSettings {
+                style,
+                charging_bolt: false,
+                ..IconSettings::default()
+            };
+            assert_eq!(frames_change_icon(&settings), vec![false; 3], "{:?}", style);
+        }
+    }
+
+    #[test]
+    fn animated_styles_change_the_icon_while_charging() {
+        let settings = IconSettings {
+            style: IconStyle::Gauge,
+            ..IconSettings::default()
+        };
+        assert_eq!(frames_change_icon(&settings), vec![true; 3]);
+
+        let settings = IconSettings {
+            style: IconStyle::Text,
+            charging_bolt: true,
+            ..IconSettings::default()
+        };
+        assert_eq!(frames_change_icon(&settings), vec![false, true, false]);
+    }
+}
@@ -0,0 +1,221 @@
+use crate::paths;
+use image::{imageops, RgbaImage};
+use notify_debouncer_mini::{new_debouncer, notify::RecursiveMode, DebounceEventResult, Debouncer};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+pub const MANIFEST_FILE: &str = "manifest.toml";
+
+// States a pack can provide an icon for, as named by BatteryStatus::state_name
+const STATES: &[&str] = &[
+    "discharging",
+    "charging",
+    "full",
+    "asleep",
+    "waking",
+    "not_found",
+    "unknown",
+];
+
+pub type PackWatcher = Debouncer<notify_debouncer_mini::notify::RecommendedWatcher>;
+
+// manifest.toml of an icon pack. File names are relative to the pack
+// directory and may be PNG or SVG.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Manifest {
+    // Icons for whole states, e.g. charging = "charging.svg"
+    #[serde(default)]
+    states: BTreeMap<String, String>,
+    // Icons for level ranges, used when the state has no icon of its own
+    #[serde(default)]
+    levels: Vec<LevelEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LevelEntry {
+    from: u8,
+    to: u8,
+    file: String,
+}
+
+enum PackImage {
+    Raster(RgbaImage),
+    // Kept as source and rasterised at the size the tray asks for
+    Svg(Vec<u8>),
+}
+
+impl PackImage {
+    fn load(path: &Path) -> Result<Self, String> {
+        let bytes =
+            fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let is_svg = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("svg"));
+
+        if is_svg {
+            // Parse once up front so broken files are reported on load
+            rasterize_svg(&bytes, 16).map_err(|e| format!("{}: {}", path.display(), e))?;
+            Ok(PackImage::Svg(bytes))
+        } else {
+            let img = image::load_from_memory(&bytes)
+                .map_err(|e| format!("Failed to decode {}: {}", path.display(), e))?;
+            Ok(PackImage::Raster(img.to_rgba8()))
+        }
+    }
+
+    fn render(&self, size: u32) -> Option<RgbaImage> {
+        match self {
+            PackImage::Raster(img) if img.width() == size && img.height() == size => {
+                Some(img.clone())
+            }
+            PackImage::Raster(img) => Some(imageops::resize(
+                img,
+                size,
+                size,
+                imageops::FilterType::Lanczos3,
+            )),
+            PackImage::Svg(bytes) => rasterize_svg(bytes, size).ok(),
+        }
+    }
+}
+
+fn rasterize_svg(bytes: &[u8], size: u32) -> Result<RgbaImage, String> {
+    use resvg::{tiny_skia, usvg};
+
+    let tree = usvg::Tree::from_data(bytes, &usvg::Options::default())
+        .map_err(|e| format!("Invalid SVG: {}", e))?;
+    let mut pixmap = tiny_skia::Pixmap::new(size, size).ok_or("Invalid icon size")?;
+    let svg_size = tree.size();
+    let transform = tiny_skia::Transform::from_scale(
+        size as f32 / svg_size.width(),
+        size as f32 / svg_size.height(),
+    );
+    resvg::render(&tree, transform, &mut pixmap.as_mut());
+
+    // tiny-skia stores premultiplied alpha
+    let pixels = pixmap
+        .pixels()
+        .iter()
+        .flat_map(|pixel| {
+            let color = pixel.demultiply();
+            [color.red(), color.green(), color.blue(), color.alpha()]
+        })
+        .collect();
+    RgbaImage::from_raw(size, size, pixels).ok_or_else(|| "Invalid SVG output".to_string())
+}
+
+// A validated icon pack with all of its images loaded
+pub struct IconPack {
+    states: BTreeMap<String, PackImage>,
+    levels: Vec<(u8, u8, PackImage)>,
+}
+
+impl IconPack {
+    pub fn load(dir: &Path) -> Result<Self, String> {
+        let manifest_path = dir.join(MANIFEST_FILE);
+        let content = fs::read_to_string(&manifest_path)
+            .map_err(|e| format!("Failed to read {}: {}", manifest_path.display(), e))?;
+        let manifest: Manifest = toml::from_str(&content)
+            .map_err(|e| format!("Invalid {}: {}", manifest_path.display(), e))?;
+
+        let mut states = BTreeMap::new();
+        for (state, file) in manifest.states {
+            if !STATES.contains(&state.as_str()) {
+                return Err(format!(
+                    "Unknown state \"{}\" in {} (expected one of {})",
+                    state,
+                    MANIFEST_FILE,
+                    STATES.join(", ")
+                ));
+            }
+            states.insert(state, PackImage::load(&dir.join(file))?);
+        }
+
+        let mut levels = Vec::new();
+        for entry in manifest.levels {
+            if entry.from > entry.to || entry.to > 100 {
+                return Err(format!(
+                    "Invalid level range {}-{} in {}",
+                    entry.from, entry.to, MANIFEST_FILE
+                ));
+            }
+            if let Some((from, to, _)) = levels
+                .iter()
+                .find(|(from, to, _)| entry.from <= *to && *from <= entry.to)
+            {
+                return Err(format!(
+                    "Level range {}-{} overlaps {}-{} in {}",
+                    entry.from, entry.to, from, to, MANIFEST_FILE
+                ));
+            }
+            levels.push((
+                entry.from,
+                entry.to,
+                PackImage::load(&dir.join(entry.file))?,
+            ));
+        }
+
+        if states.is_empty() && levels.is_empty() {
+            return Err(format!("{} does not list any icons", MANIFEST_FILE));
+        }
+
+        Ok(IconPack { states, levels })
+    }
+
+    // Icon for a state and level, or None when the pack doesn't cover it
+    // and the built-in renderer should be used
+    pub fn render(&self, state: &str, level: Option<u8>, size: u32) -> Option<RgbaImage> {
+        self.image(state, level)?.render(size)
+    }
+
+    pub fn covers(&self, state: &str, level: Option<u8>) -> bool {
+        self.image(state, level).is_some()
+    }
+
+    fn image(&self, state: &str, level: Option<u8>) -> Option<&PackImage> {
+        if let Some(image) = self.states.get(state) {
+            return Some(image);
+        }
+        let level = level?;
+        self.levels
+            .iter()
+            .find(|(from, to, _)| (*from..=*to).contains(&level))
+            .map(|(_, _, image)| image)
+    }
+}
+
+// Resolves the configured pack directory; relative paths are taken from
+// the settings directory
+pub fn resolve_dir(pack: &Path) -> Option<PathBuf> {
+    if pack.is_absolute() {
+        Some(pack.to_path_buf())
+    } else {
+        paths::config_dir().map(|dir| dir.join(pack))
+    }
+}
+
+// Calls `on_change` whenever a file in the pack directory changes. The
+// returned watcher must be kept alive.
+pub fn watch(dir: &Path, on_change: impl Fn() + Send + 'static) -> Result<PackWatcher, String> {
+    let mut debouncer = new_debouncer(
+        Duration::from_millis(500),
+        move |result: DebounceEventResult| {
+            if result.is_ok_and(|events| !events.is_empty()) {
+                on_change();
+            }
+        },
+    )
+    .map_err(|e| format!("Failed to watch icon pack: {}", e))?;
+
+    debouncer
+        .watcher()
+        .watch(dir, RecursiveMode::NonRecursive)
+        .map_err(|e| format!("Failed to watch {}: {}", dir.display(), e))?;
+
+    Ok(debouncer)
+}
@@ -0,0 +1,213 @@
+use crate::i18n;
+use crate::mouse_battery::{BatteryStatus, MouseModel};
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
+
+// A cached level older than this is dropped rather than shown, e.g. for a
+// mouse that has been asleep since yesterday
+pub const MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+// Last successful percentage reading for a device
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LastKnown {
+    pub percentage: u8,
+    pub charging: bool,
+    pub recorded_at: SystemTime,
+}
+
+impl LastKnown {
+    pub fn age(&self) -> Duration {
+        self.recorded_at.elapsed().unwrap_or_default()
+    }
+
+    // Human readable age, e.g. "just now", "14 min ago", "3 h ago"
+    pub fn format_age(&self) -> String {
+        let secs = self.age().as_secs();
+        if secs < 60 {
+            return i18n::tr("age-just-now");
+        }
+        let (id, count) = if secs < 60 * 60 {
+            ("age-minutes", secs / 60)
+        } else if secs < 24 * 60 * 60 {
+            ("age-hours", secs / (60 * 60))
+        } else {
+            ("age-days", secs / (24 * 60 * 60))
+        };
+        i18n::tr_with(id, &[("count", count.into())])
+    }
+}
+
+// A battery status together with the cached reading used to present it
+// when the mouse itself can't report a level (asleep, waking up, unknown)
+#[derive(Debug, Clone)]
+pub struct BatteryReading {
+    pub status: BatteryStatus,
+    pub last_known: Option<LastKnown>,
+    // Estimated time until empty (or full while charging)
+    pub remaining: Option<Duration>,
+    // Only looked up when a template shows it
+    pub firmware: Option<String>,
+    // Unfiltered percentage as reported by the mouse, for diagnostics
+    pub raw_percentage: Option<u8>,
+}
+
+impl BatteryReading {
+    // The status is not live but we still have a cached level to show
+    pub fn is_stale(&self) -> bool {
+        self.last_known.is_some()
+    }
+
+    // Product ID in hex, e.g. 2034, naming the device on D-Bus and in the
+    // HTTP API
+    pub fn device_id(&self) -> Option<String> {
+        let product_id = self.status.get_mouse_model()?.product_id()?;
+        Some(format!("{:04x}", product_id))
+    }
+
+    // Level to display: live when available, otherwise the cached one
+    pub fn level(&self) -> Option<u8> {
+        self.last_known
+            .map(|last| last.percentage)
+            .or(self.status.percentage())
+    }
+
+    pub fn get_tooltip(&self) -> String {
+        let last = match self.last_known {
+            Some(last) => last,
+            None => return self.status.get_tooltip(),
+        };
+
+        let state = match self.status {
+            BatteryStatus::Asleep { .. } => "stale-asleep",
+            BatteryStatus::WakingUp { .. } => "stale-waking",
+            _ => "stale-unknown",
+        };
+
+        i18n::tr_with(
+            "status-stale",
+            &[
+                ("name", self.status.display_name().into()),
+                ("percent", last.percentage.into()),
+                ("age", last.format_age().into()),
+                ("state", i18n::tr(state).into()),
+            ],
+        )
+    }
+}
+
+// Per-device cache of the last successful reading
+#[derive(Debug, Default)]
+pub struct LastKnownCache {
+    readings: HashMap<MouseModel, LastKnown>,
+}
+
+impl LastKnownCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, mouse_model: &MouseModel) -> Option<LastKnown> {
+        self.readings.get(mouse_model).copied()
+    }
+
+    // Records live readings and attaches the cached level to readings
+    // that don't carry one, as long as it isn't older than MAX_AGE
+    pub fn observe(&mut self, status: BatteryStatus) -> BatteryReading {
+        let live = match status {
+            BatteryStatus::Normal { percentage, .. } => Some((percentage, false)),
+            BatteryStatus::Charging { percentage, .. } => Some((percentage, true)),
+            BatteryStatus::FullyCharged { .. } => Some((100, true)),
+            _ => None,
+        };
+
+        let last_known = match (live, status.get_mouse_model()) {
+            (Some((percentage, charging)), Some(mouse_model)) => {
+                self.readings.insert(
+                    mouse_model,
+                    LastKnown {
+                        percentage,
+                        charging,
+                        recorded_at: SystemTime::now(),
+                    },
+                );
+                None
+            }
+            (None, Some(mouse_model)) => match self.get(&mouse_model) {
+                Some(last) if last.age() > MAX_AGE => {
+                    self.readings.remove(&mouse_model);
+                    None
+                }
+                last => last,
+            },
+            _ => None,
+        };
+
+        BatteryReading {
+            status,
+            last_known,
+            remaining: None,
+            firmware: None,
+            raw_percentage: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn model() -> MouseModel {
+        MouseModel::from_product_id(0x2034)
+    }
+
+    #[test]
+    fn keeps_level_while_asleep() {
+        let mut cache = LastKnownCache::new();
+        cache.observe(BatteryStatus::Normal {
+            percentage: 62,
+            mouse_model: model(),
+        });
+
+        let reading = cache.observe(BatteryStatus::Asleep {
+            mouse_model: model(),
+        });
+        assert_eq!(reading.level(), Some(62));
+        assert!(reading.is_stale());
+    }
+
+    #[test]
+    fn drops_level_older_than_max_age() {
+        let mut cache = LastKnownCache::new();
+        cache.readings.insert(
+            model(),
+            LastKnown {
+                percentage: 62,
+                charging: false,
+                recorded_at: SystemTime::now() - MAX_AGE - Duration::from_secs(60),
+            },
+        );
+
+        let reading = cache.observe(BatteryStatus::Asleep {
+            mouse_model: model(),
+        });
+        assert_eq!(reading.level(), None);
+        assert!(!reading.is_stale());
+        assert_eq!(cache.get(&model()), None);
+    }
+
+    #[test]
+    fn live_reading_replaces_cached_level() {
+        let mut cache = LastKnownCache::new();
+        cache.observe(BatteryStatus::Normal {
+            percentage: 62,
+            mouse_model: model(),
+        });
+
+        let reading = cache.observe(BatteryStatus::Charging {
+            percentage: 63,
+            mouse_model: model(),
+        });
+        assert!(!reading.is_stale());
+        assert_eq!(cache.get(&model()).map(|last| last.charging), Some(true));
+    }
+}
@@ -1,36 +1,98 @@
+mod alerts;
+mod autostart;
+mod calibration;
+mod cli;
+mod clicks;
+#[cfg(target_os = "linux")]
+mod daemon;
+#[cfg(target_os = "linux")]
+mod dbus_service;
+mod estimate;
+mod history;
+mod hooks;
+mod http_api;
+mod i18n;
+mod icon;
+mod icon_pack;
+mod last_known;
+mod metrics;
+mod monitor;
 mod mouse_battery;
-
-use ab_glyph::{FontRef, PxScale};
-use image::{ImageBuffer, Rgba, RgbaImage};
-use imageproc::drawing::draw_text_mut;
-use mouse_battery::{BatteryStatus, MouseBattery};
+mod paths;
+mod settings;
+mod settings_window;
+mod smoothing;
+mod status_bar;
+mod template;
+mod theme;
+mod tray_menu;
+#[cfg(target_os = "linux")]
+mod upower;
+
+use clicks::{ClickAction, ClickDebouncer};
+use icon::IconCache;
+use icon_pack::{IconPack, PackWatcher};
+use last_known::BatteryReading;
+use monitor::Monitor;
+use mouse_battery::MouseBattery;
+use settings::{IconSettings, Settings, SettingsWatcher};
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use tauri::{
     image::Image,
-    menu::{CheckMenuItemBuilder, MenuBuilder, MenuItemBuilder},
     tray::{MouseButton, MouseButtonState, TrayIconBuilder, TrayIconEvent},
     AppHandle, Manager, Runtime,
 };
-use tauri_plugin_autostart::{MacosLauncher, ManagerExt};
-use tokio::time::{interval, Duration};
+use tauri_plugin_autostart::MacosLauncher;
+use tauri_plugin_clipboard_manager::ClipboardExt;
+use tauri_plugin_opener::OpenerExt;
+use theme::Theme;
+use tokio::sync::{mpsc, watch};
+use tokio::time::{interval, MissedTickBehavior};
+
+pub use cli::run_cli;
+
+// Time each animation frame is shown
+const ANIMATION_FRAME: Duration = Duration::from_millis(500);
+
+// How long "Pause monitoring" stops polling
+const PAUSE_DURATION: Duration = Duration::from_secs(60 * 60);
 
 pub struct AppState {
-    mouse_battery: Arc<Mutex<MouseBattery>>,
+    monitor: Mutex<Monitor>,
     autostart_enabled: Arc<Mutex<bool>>,
+    settings: watch::Sender<Settings>,
+    settings_watcher: Mutex<Option<SettingsWatcher>>,
+    // Colour scheme detected from the system
+    theme: watch::Sender<Theme>,
+    icon_cache: Mutex<IconCache>,
+    // Custom icons from `icon.pack`, with the directory they came from
+    icon_pack: Mutex<Option<IconPack>>,
+    icon_pack_dir: Mutex<Option<PathBuf>>,
+    icon_pack_watcher: Mutex<Option<PackWatcher>>,
+    // Latest reading and animation step, for redrawing between polls
+    last_reading: Mutex<Option<BatteryReading>>,
+    animation_tick: Mutex<u64>,
+    // Latest reading of every connected device, the icon's one first.
+    // Also feeds the D-Bus service.
+    devices: watch::Sender<Vec<BatteryReading>>,
+    // Polling is skipped until then
+    paused_until: Mutex<Option<Instant>>,
+    clicks: Mutex<ClickDebouncer>,
 }
 
 fn log_error(msg: &str) {
     #[cfg(windows)]
     {
-        if let Ok(appdata) = std::env::var("LOCALAPPDATA") {
+        if let Some(log_dir) = paths::log_dir() {
             use std::fs::{create_dir_all, OpenOptions};
             use std::io::Write;
-            let log_dir = format!("{}\\ModelD2ProBattery", appdata);
             let _ = create_dir_all(&log_dir);
             if let Ok(mut file) = OpenOptions::new()
                 .create(true)
                 .append(true)
-                .open(format!("{}\\error.log", log_dir))
+                .open(log_dir.join("error.log"))
             {
                 let _ = writeln!(file, "{}", msg);
             }
@@ -42,13 +104,22 @@ fn log_error(msg: &str) {
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     log_error("Starting application...");
+    if std::env::args().any(|arg| arg == autostart::LAUNCH_ARG || arg == "--minimized") {
+        log_error("Started at login");
+    }
 
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
+        .plugin(tauri_plugin_clipboard_manager::init())
         .plugin(tauri_plugin_autostart::init(
             MacosLauncher::LaunchAgent,
-            Some(vec![]),
+            Some(vec![autostart::LAUNCH_ARG]),
         ))
+        .invoke_handler(tauri::generate_handler![
+            settings_window::get_settings,
+            settings_window::validate_settings,
+            settings_window::save_settings,
+        ])
         .setup(|app| {
             log_error("Setting up application...");
 
@@ -56,7 +127,7 @@ pub fn run() {
             let mouse_battery = match MouseBattery::new() {
                 Ok(mb) => {
                     log_error("Mouse battery monitor initialized successfully");
-                    Arc::new(Mutex::new(mb))
+                    mb
                 }
                 Err(e) => {
                     let err_msg = format!("Failed to initialize mouse battery: {}", e);
@@ -65,16 +136,52 @@ pub fn run() {
                 }
             };
 
+            // Load settings, falling back to the defaults if the file is invalid
+            let settings = match settings::load() {
+                Ok(settings) => settings,
+                Err(e) => {
+                    log_error(&format!("Failed to load settings: {}", e));
+                    alerts::notify_user(&i18n::tr("error-settings"), &e);
+                    Settings::default()
+                }
+            };
+
             // Check autostart status
-            let autostart_manager = app.autolaunch();
-            let autostart_enabled =
-                Arc::new(Mutex::new(autostart_manager.is_enabled().unwrap_or(false)));
+            let autostart_enabled = autostart::is_enabled(app.handle()).unwrap_or_else(|e| {
+                log_error(&format!("Failed to read autostart state: {}", e));
+                false
+            });
+            let autostart_enabled = Arc::new(Mutex::new(autostart_enabled));
 
             // Store state in app
             app.manage(AppState {
-                mouse_battery: mouse_battery.clone(),
+                monitor: Mutex::new(Monitor::new(mouse_battery, &settings)),
                 autostart_enabled: autostart_enabled.clone(),
+                settings: watch::Sender::new(settings.clone()),
+                settings_watcher: Mutex::new(None),
+                theme: watch::Sender::new(Theme::Dark),
+                icon_cache: Mutex::new(IconCache::new()),
+                icon_pack: Mutex::new(None),
+                icon_pack_dir: Mutex::new(None),
+                icon_pack_watcher: Mutex::new(None),
+                last_reading: Mutex::new(None),
+                animation_tick: Mutex::new(0),
+                devices: watch::Sender::new(Vec::new()),
+                paused_until: Mutex::new(None),
+                clicks: Mutex::new(ClickDebouncer::new()),
             });
+            apply_settings(&app.state::<AppState>(), &settings);
+            load_icon_pack(app.handle(), &settings.icon);
+
+            // Reload settings when the file changes
+            let state = app.state::<AppState>();
+            match settings::watch(state.settings.clone(), |e| {
+                log_error(&format!("Failed to reload settings: {}", e));
+                alerts::notify_user(&i18n::tr("error-settings"), &e);
+            }) {
+                Ok(watcher) => *state.settings_watcher.lock().unwrap() = Some(watcher),
+                Err(e) => log_error(&e),
+            }
 
             log_error("Setting up system tray...");
             // Setup system tray
@@ -87,12 +194,39 @@ pub fn run() {
             log_error("Starting battery monitoring task...");
             // Start periodic battery monitoring
             let app_handle = app.handle().clone();
-            let mouse_battery_clone = mouse_battery.clone();
+            let settings_rx = app.state::<AppState>().settings.subscribe();
+            let theme_rx = app.state::<AppState>().theme.subscribe();
+            // Holds one pending refresh; more requests meanwhile are merged
+            let (refresh_tx, refresh_rx) = mpsc::channel(1);
 
             tauri::async_runtime::spawn(async move {
-                battery_monitor_task(app_handle, mouse_battery_clone).await;
+                battery_monitor_task(app_handle, settings_rx, theme_rx, refresh_rx).await;
             });
 
+            // Serve the readings to other programs over HTTP, when enabled,
+            // and on the session bus
+            let state = app.state::<AppState>();
+            tauri::async_runtime::spawn(http_api::serve(
+                state.settings.subscribe(),
+                state.devices.subscribe(),
+                refresh_tx.clone(),
+            ));
+            #[cfg(target_os = "linux")]
+            tauri::async_runtime::spawn(dbus_service::serve(
+                None,
+                state.settings.subscribe(),
+                state.devices.subscribe(),
+                refresh_tx,
+            ));
+
+            // Follow the system light/dark scheme so the icon stays visible
+            let theme_tx = app.state::<AppState>().theme.clone();
+            tauri::async_runtime::spawn(theme::watch(theme_tx));
+
+            // Animate the icon on its own timer, independent of polling
+            let app_handle = app.handle().clone();
+            tauri::async_runtime::spawn(animation_task(app_handle));
+
             log_error("Setup complete!");
             Ok(())
         })
@@ -110,235 +244,634 @@ pub fn run() {
         });
 }
 
-fn build_menu_with_status<R: Runtime>(
-    app: &AppHandle<R>,
-    battery_status: &BatteryStatus,
-    autostart_enabled: bool,
-) -> Result<tauri::menu::Menu<R>, Box<dyn std::error::Error>> {
-    // Create status menu item at the top showing mouse name and percentage
-    let status_text = battery_status.get_tooltip();
-    let status_item = MenuItemBuilder::with_id("status", &status_text)
-        .enabled(false) // Make it non-clickable (display only)
-        .build(app)?;
-
-    // Create other menu items
-    let refresh_item = MenuItemBuilder::with_id("refresh", "Refresh").build(app)?;
-    let firmware_item = MenuItemBuilder::with_id("firmware", "Show Firmware Version").build(app)?;
-
-    let autostart_item = CheckMenuItemBuilder::with_id("autostart", "Run at Startup")
-        .checked(autostart_enabled)
-        .build(app)?;
-
-    let quit_item = MenuItemBuilder::with_id("quit", "Exit").build(app)?;
-
-    // Build menu with status at the top
-    let menu = MenuBuilder::new(app)
-        .items(&[
-            &status_item,
-            &refresh_item,
-            &firmware_item,
-            &autostart_item,
-            &quit_item,
-        ])
-        .build()?;
-
-    Ok(menu)
-}
-
 fn setup_tray<R: Runtime>(
     app: &AppHandle<R>,
     autostart_enabled: Arc<Mutex<bool>>,
 ) -> Result<(), Box<dyn std::error::Error>> {
     // Get initial battery status
     let state = app.state::<AppState>();
-    let battery_status = state.mouse_battery.lock().unwrap().get_battery_status();
+    let settings = state.settings.borrow().clone();
+    let readings = read_batteries(&state, &settings);
+    let reading = &readings[0];
     let autostart_checked = *autostart_enabled.lock().unwrap();
 
     // Build menu with status at top
-    let menu = build_menu_with_status(app, &battery_status, autostart_checked)?;
-
-    // Generate text icon
-    let icon = create_text_icon(&battery_status)?;
+    let menu_state = tray_menu::MenuState {
+        readings: &readings,
+        autostart_enabled: autostart_checked,
+        paused: paused_for(&state),
+    };
+    let live_menu = tray_menu::LiveMenu::new(app, tray_menu::model(&menu_state, &settings))?;
 
     // Create tray icon
-    let tray = TrayIconBuilder::new()
-        .icon(icon)
-        .menu(&menu)
-        .tooltip(battery_status.get_tooltip())
+    let mut tray = TrayIconBuilder::new();
+    if let Some(icon) = create_tray_icon(app, reading, &settings)? {
+        tray = tray.icon(icon);
+    }
+    let tray = tray
+        .menu(live_menu.menu())
+        .show_menu_on_left_click(settings.tray.left_click == ClickAction::Menu)
+        .tooltip(tooltip_text(reading, &settings))
         .on_menu_event(move |app, event| match event.id.as_ref() {
             "refresh" => {
                 if let Err(e) = update_tray_status(app) {
                     eprintln!("Failed to refresh status: {}", e);
                 }
             }
+            "pause" => {
+                toggle_pause(app);
+            }
+            "copy_status" => {
+                copy_status(app);
+            }
+            "open_logs" => {
+                open_log_folder(app);
+            }
+            "open_history" => {
+                open_history(app);
+            }
             "firmware" => {
                 show_firmware_version(app);
             }
             "autostart" => {
                 toggle_autostart(app);
             }
+            "settings" => {
+                if let Err(e) = settings_window::open_settings_window(app) {
+                    log_error(&format!("Failed to open settings: {}", e));
+                }
+            }
             "quit" => {
                 app.exit(0);
             }
             _ => {}
         })
-        .on_tray_icon_event(|_tray, event| {
-            if let TrayIconEvent::Click {
-                button: MouseButton::Left,
-                button_state: MouseButtonState::Up,
-                ..
-            } = event
-            {
-                // Optional: Show menu on left click
-                // tray.popup_menu();
-            }
-        })
+        .on_tray_icon_event(|tray, event| on_tray_click(tray.app_handle(), event))
         .build(app)?;
 
-    // Store tray icon in app state
+    // Store tray icon and menu in app state
     app.manage(tray);
+    app.manage(Mutex::new(live_menu));
 
     Ok(())
 }
 
-fn create_text_icon(status: &BatteryStatus) -> Result<Image<'static>, Box<dyn std::error::Error>> {
-    let text = match status {
-        BatteryStatus::Normal { percentage, .. } => format!("{}", percentage),
-        BatteryStatus::Charging { percentage, .. } => format!("{}", percentage),
-        BatteryStatus::FullyCharged { .. } => "100".to_string(),
-        BatteryStatus::Asleep { .. } => "ZZZ".to_string(),
-        BatteryStatus::WakingUp { .. } => "...".to_string(),
-        BatteryStatus::NotFound => "N/A".to_string(),
-        BatteryStatus::Unknown { .. } => "???".to_string(),
-    };
+// Runs the configured click actions. A left click waits for a possible
+// second click when double-click has an action of its own.
+fn on_tray_click<R: Runtime>(app: &AppHandle<R>, event: TrayIconEvent) {
+    let state = app.state::<AppState>();
+    let tray_settings = state.settings.borrow().tray.clone();
+
+    match event {
+        // The menu is about to open; make sure its check marks are current.
+        // Linux trays don't report clicks, so there the poll re-reads it.
+        TrayIconEvent::Click {
+            button,
+            button_state: MouseButtonState::Down,
+            ..
+        } if button == MouseButton::Right
+            || (button == MouseButton::Left && tray_settings.left_click == ClickAction::Menu) =>
+        {
+            if refresh_autostart(app) {
+                if let Err(e) = redraw_tray(app) {
+                    eprintln!("Failed to update tray status: {}", e);
+                }
+            }
+        }
+        TrayIconEvent::Click {
+            button: MouseButton::Left,
+            button_state: MouseButtonState::Up,
+            ..
+        } => {
+            // The system opens the menu itself
+            if tray_settings.left_click == ClickAction::Menu {
+                return;
+            }
+            if tray_settings.double_click == ClickAction::None {
+                run_click_action(app, tray_settings.left_click);
+                return;
+            }
 
-    // Create a larger 256x256 image with transparent background for better quality
-    let mut img: RgbaImage = ImageBuffer::from_pixel(256, 256, Rgba([0, 0, 0, 0]));
+            let token = state.clicks.lock().unwrap().defer();
+            let app = app.clone();
+            tauri::async_runtime::spawn(async move {
+                tokio::time::sleep(clicks::DOUBLE_CLICK_WINDOW).await;
+                let current = app
+                    .state::<AppState>()
+                    .clicks
+                    .lock()
+                    .unwrap()
+                    .is_current(token);
+                if current {
+                    run_click_action(&app, tray_settings.left_click);
+                }
+            });
+        }
+        TrayIconEvent::DoubleClick {
+            button: MouseButton::Left,
+            ..
+        } => {
+            state.clicks.lock().unwrap().cancel();
+            run_click_action(app, tray_settings.double_click);
+        }
+        TrayIconEvent::Click {
+            button: MouseButton::Middle,
+            button_state: MouseButtonState::Up,
+            ..
+        } => {
+            run_click_action(app, tray_settings.middle_click);
+        }
+        _ => {}
+    }
+}
+
+fn run_click_action<R: Runtime>(app: &AppHandle<R>, action: ClickAction) {
+    let state = app.state::<AppState>();
+    if !state
+        .clicks
+        .lock()
+        .unwrap()
+        .should_run(action, Instant::now())
+    {
+        return;
+    }
+
+    match action {
+        ClickAction::None | ClickAction::Menu => {}
+        ClickAction::Refresh => {
+            if let Err(e) = update_tray_status(app) {
+                eprintln!("Failed to refresh status: {}", e);
+            }
+        }
+        ClickAction::Popup => {
+            let settings = state.settings.borrow().clone();
+            let lines: Vec<String> = state
+                .devices
+                .borrow()
+                .iter()
+                .map(|reading| tooltip_text(reading, &settings))
+                .collect();
+            alerts::notify_user(&i18n::tr("popup-title"), &lines.join("\n"));
+        }
+        ClickAction::ToggleNotifications => toggle_notifications(&state),
+        ClickAction::RunHook => {
+            let command = state.settings.borrow().hooks.on_click.clone();
+            let reading = state.last_reading.lock().unwrap().clone();
+            if let (Some(command), Some(reading)) = (command, reading) {
+                if let Err(e) = hooks::run_hook(&command, &reading, "click") {
+                    log_error(&e);
+                }
+            }
+        }
+    }
+}
 
-    // Load a font
-    let font_data = include_bytes!("../assets/DejaVuSans.ttf");
-    let font = FontRef::try_from_slice(font_data).map_err(|_| "Failed to load font")?;
+// Flips notifications.enabled and saves it, like the settings window does
+fn toggle_notifications(state: &AppState) {
+    let mut settings = state.settings.borrow().clone();
+    settings.notifications.enabled = !settings.notifications.enabled;
+    if let Err(e) = settings::save(&settings) {
+        log_error(&format!("Failed to save settings: {}", e));
+        return;
+    }
+    let enabled = settings.notifications.enabled;
+    state.settings.send_replace(settings);
 
-    // Use much larger font size for better readability in system tray
-    let scale = if text.len() <= 2 {
-        PxScale::from(200.0) // Very large for 2 characters (like "68")
-    } else if text.len() == 3 {
-        PxScale::from(110.0) // Large for 3 characters (like "100")
+    let message = if enabled {
+        "notifications-on"
     } else {
-        PxScale::from(80.0) // Smaller for 4+ characters (like "N/A")
+        "notifications-off"
     };
+    alerts::notify_user(&i18n::tr("popup-title"), &i18n::tr(message));
+}
 
-    // Draw white text with good visibility
-    let white = Rgba([255u8, 255u8, 255u8, 255u8]);
+// Returns the tray icon for a reading, or None when it would look the
+// same as the one already shown
+fn create_tray_icon<R: Runtime>(
+    app: &AppHandle<R>,
+    reading: &BatteryReading,
+    settings: &Settings,
+) -> Result<Option<Image<'static>>, Box<dyn std::error::Error>> {
+    let state = app.state::<AppState>();
+    let icon_settings = &settings.icon;
+
+    // Render at the size the tray shows rather than scaling a large image down
+    let scale_factor = app
+        .primary_monitor()
+        .ok()
+        .flatten()
+        .map(|monitor| monitor.scale_factor())
+        .unwrap_or(1.0);
+    let size = icon::tray_icon_size(icon_settings.size, scale_factor);
+    let theme = icon_settings.theme.resolve(*state.theme.borrow());
+    let frame = icon::animation_frame(
+        reading,
+        icon_settings,
+        settings.notifications.critical_threshold,
+        *state.animation_tick.lock().unwrap(),
+    );
+
+    let pack = state.icon_pack.lock().unwrap();
+    let (img, changed) = state.icon_cache.lock().unwrap().get(
+        reading,
+        icon_settings,
+        size,
+        theme,
+        pack.as_ref(),
+        frame,
+    )?;
+    if !changed {
+        return Ok(None);
+    }
 
-    // Better centering for larger canvas
-    let x_offset = if text.len() <= 2 {
-        40
-    } else if text.len() == 3 {
-        30
-    } else {
-        20
+    // Hand the RGBA pixels over directly instead of a PNG round trip
+    Ok(Some(Image::new_owned(img.into_raw(), size, size)))
+}
+
+// Loads the configured icon pack and reloads it whenever its files change
+fn load_icon_pack<R: Runtime>(app: &AppHandle<R>, icon_settings: &IconSettings) {
+    let state = app.state::<AppState>();
+    let dir = icon_settings
+        .pack
+        .as_deref()
+        .and_then(icon_pack::resolve_dir);
+    {
+        let mut current = state.icon_pack_dir.lock().unwrap();
+        if *current == dir {
+            return;
+        }
+        *current = dir.clone();
+    }
+
+    *state.icon_pack_watcher.lock().unwrap() = None;
+    reload_icon_pack(app, dir.as_deref());
+
+    let Some(dir) = dir else {
+        return;
     };
-    let y_offset = 40;
+    let app_handle = app.clone();
+    let watched = dir.clone();
+    match icon_pack::watch(&dir, move || {
+        reload_icon_pack(&app_handle, Some(&watched));
+        // Only the icons changed, so the last readings are drawn again
+        if let Err(e) = redraw_tray(&app_handle) {
+            log_error(&format!("Failed to redraw the tray icon: {}", e));
+        }
+    }) {
+        Ok(watcher) => *state.icon_pack_watcher.lock().unwrap() = Some(watcher),
+        Err(e) => log_error(&e),
+    }
+}
+
+// Replaces the loaded icon pack; a pack that fails validation is reported
+// and the built-in icons are used instead
+fn reload_icon_pack<R: Runtime>(app: &AppHandle<R>, dir: Option<&Path>) {
+    let state = app.state::<AppState>();
+    let pack = dir.and_then(|dir| match IconPack::load(dir) {
+        Ok(pack) => Some(pack),
+        Err(e) => {
+            log_error(&format!("Failed to load icon pack: {}", e));
+            alerts::notify_user(&i18n::tr("error-icon-pack"), &e);
+            None
+        }
+    });
+    *state.icon_pack.lock().unwrap() = pack;
+    state.icon_cache.lock().unwrap().clear();
+}
 
-    draw_text_mut(&mut img, white, x_offset, y_offset, scale, &font, &text);
+// Pushes settings that live outside the shared settings channel into the monitor
+fn apply_settings(state: &AppState, settings: &Settings) {
+    i18n::set_language(&settings.language);
+    state.monitor.lock().unwrap().apply_settings(settings);
+}
 
-    // Convert to PNG bytes
-    let mut png_bytes = Vec::new();
-    img.write_to(
-        &mut std::io::Cursor::new(&mut png_bytes),
-        image::ImageFormat::Png,
-    )?;
+// Reads every connected mouse and keeps the readings for redraws
+fn read_batteries(state: &AppState, settings: &Settings) -> Vec<BatteryReading> {
+    let readings = state.monitor.lock().unwrap().read_all(settings);
+    *state.last_reading.lock().unwrap() = Some(readings[0].clone());
+    state.devices.send_replace(readings.clone());
+    readings
+}
 
-    let icon = Image::from_bytes(&png_bytes)?;
-    Ok(icon)
+fn tooltip_text(reading: &BatteryReading, settings: &Settings) -> String {
+    template::render_or(
+        settings.icon.tooltip.as_deref(),
+        reading,
+        reading.get_tooltip(),
+    )
 }
 
 fn update_tray_status<R: Runtime>(app: &AppHandle<R>) -> Result<(), Box<dyn std::error::Error>> {
     let state = app.state::<AppState>();
-    let battery_status = state.mouse_battery.lock().unwrap().get_battery_status();
-    let autostart_enabled = state.autostart_enabled.lock().unwrap();
-    let autostart_checked = *autostart_enabled;
+    let settings = state.settings.borrow().clone();
+    let readings = read_batteries(&state, &settings);
+    refresh_autostart(app);
+    show_readings(app, &readings, &settings)
+}
+
+// Redraws the icon, tooltip and menu from the latest readings without
+// polling the mouse
+fn redraw_tray<R: Runtime>(app: &AppHandle<R>) -> Result<(), Box<dyn std::error::Error>> {
+    let state = app.state::<AppState>();
+    let settings = state.settings.borrow().clone();
+    let readings = state.devices.borrow().clone();
+    if readings.is_empty() {
+        return Ok(());
+    }
+    show_readings(app, &readings, &settings)
+}
+
+fn show_readings<R: Runtime>(
+    app: &AppHandle<R>,
+    readings: &[BatteryReading],
+    settings: &Settings,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let state = app.state::<AppState>();
+    let reading = &readings[0];
+    let autostart_checked = *state.autostart_enabled.lock().unwrap();
 
     // Get tray icon
     if let Some(tray) = app.try_state::<tauri::tray::TrayIcon>() {
-        let icon = create_text_icon(&battery_status)?;
-
-        // Rebuild menu with updated status
-        let menu = build_menu_with_status(app, &battery_status, autostart_checked)?;
+        // Only swap the icon when it looks different
+        if let Some(icon) = create_tray_icon(app, reading, settings)? {
+            tray.set_icon(Some(icon))?;
+        }
 
-        tray.set_icon(Some(icon))?;
-        tray.set_tooltip(Some(&battery_status.get_tooltip()))?;
-        tray.set_menu(Some(menu))?;
+        tray.set_tooltip(Some(&tooltip_text(reading, settings)))?;
+
+        // Update the menu in place; only a changed layout needs a new one
+        let menu_state = tray_menu::MenuState {
+            readings,
+            autostart_enabled: autostart_checked,
+            paused: paused_for(&state),
+        };
+        if let Some(live_menu) = app.try_state::<Mutex<tray_menu::LiveMenu<R>>>() {
+            let mut live_menu = live_menu.lock().unwrap();
+            if live_menu.update(app, tray_menu::model(&menu_state, settings))? {
+                tray.set_menu(Some(live_menu.menu().clone()))?;
+            }
+        }
     }
 
     Ok(())
 }
 
+// Time left while monitoring is paused
+fn paused_for(state: &AppState) -> Option<Duration> {
+    let until = (*state.paused_until.lock().unwrap())?;
+    until
+        .checked_duration_since(Instant::now())
+        .filter(|left| !left.is_zero())
+}
+
+fn toggle_pause<R: Runtime>(app: &AppHandle<R>) {
+    let state = app.state::<AppState>();
+    let paused = paused_for(&state).is_some();
+    *state.paused_until.lock().unwrap() = if paused {
+        None
+    } else {
+        Some(Instant::now() + PAUSE_DURATION)
+    };
+
+    let result = if paused {
+        update_tray_status(app)
+    } else {
+        redraw_tray(app)
+    };
+    if let Err(e) = result {
+        eprintln!("Failed to update tray status: {}", e);
+    }
+}
+
+// Copies the status of every device, one per line
+fn copy_status<R: Runtime>(app: &AppHandle<R>) {
+    let state = app.state::<AppState>();
+    let settings = state.settings.borrow().clone();
+    let lines: Vec<String> = state
+        .devices
+        .borrow()
+        .iter()
+        .map(|reading| tooltip_text(reading, &settings))
+        .collect();
+
+    if let Err(e) = app.clipboard().write_text(lines.join("\n")) {
+        log_error(&format!("Failed to copy status: {}", e));
+    }
+}
+
+fn open_log_folder<R: Runtime>(app: &AppHandle<R>) {
+    let Some(dir) = paths::log_dir() else {
+        return;
+    };
+    let _ = std::fs::create_dir_all(&dir);
+    open_path(app, &dir);
+}
+
+fn open_history<R: Runtime>(app: &AppHandle<R>) {
+    match history::history_path().filter(|path| path.exists()) {
+        Some(path) => open_path(app, &path),
+        None => alerts::notify_user(&i18n::tr("menu-open-history"), &i18n::tr("history-missing")),
+    }
+}
+
+// Opens a file or folder with the system's default application
+fn open_path<R: Runtime>(app: &AppHandle<R>, path: &Path) {
+    if let Err(e) = app.opener().open_path(path.to_string_lossy(), None::<&str>) {
+        log_error(&format!("Failed to open {}: {}", path.display(), e));
+        alerts::notify_user(
+            &i18n::tr_with("error-open", &[("path", path.display().to_string().into())]),
+            &e.to_string(),
+        );
+    }
+}
+
 fn show_firmware_version<R: Runtime>(app: &AppHandle<R>) {
     let state = app.state::<AppState>();
-    let mouse_battery = state.mouse_battery.lock().unwrap();
+    let monitor = state.monitor.lock().unwrap();
+    let mouse_battery = &monitor.mouse_battery;
 
     // Get the detected mouse model
     let mouse_name = mouse_battery
         .get_detected_model()
-        .map(|m| m.name())
-        .unwrap_or("Mouse");
+        .map(|m| m.name().to_string())
+        .unwrap_or_else(|| i18n::tr("mouse-generic"));
 
     let firmware = mouse_battery.get_firmware_version();
 
     let message = match firmware {
-        Some(version) => format!("Firmware Version: {}", version),
-        None => "Unable to retrieve firmware version. Is the mouse connected?".to_string(),
+        Some(version) => i18n::tr_with("firmware-version", &[("version", version.into())]),
+        None => i18n::tr("firmware-unavailable"),
     };
 
     // Use a notification or dialog
-    #[cfg(windows)]
-    {
-        use std::process::Command;
-        let _ = Command::new("msg")
-            .args(&["*", &format!("{}\n\n{}", mouse_name, message)])
-            .spawn();
-    }
+    alerts::notify_user(&mouse_name, &message);
 
     println!("{}", message);
 }
 
 fn toggle_autostart<R: Runtime>(app: &AppHandle<R>) {
     let state = app.state::<AppState>();
-    let autostart_manager = app.autolaunch();
+    // Start from the system's state, which may have been changed elsewhere
+    refresh_autostart(app);
 
     let mut autostart_enabled = state.autostart_enabled.lock().unwrap();
     let new_state = !*autostart_enabled;
 
-    let result = if new_state {
-        autostart_manager.enable()
-    } else {
-        autostart_manager.disable()
-    };
-
-    match result {
-        Ok(_) => {
+    match autostart::set_enabled(app, new_state) {
+        Ok(()) => {
             *autostart_enabled = new_state;
             println!("Autostart toggled to: {}", new_state);
         }
         Err(e) => {
-            eprintln!("Failed to toggle autostart: {}", e);
+            log_error(&format!("Failed to toggle autostart: {}", e));
+            alerts::notify_user(&i18n::tr("error-autostart"), &e);
         }
     }
+    drop(autostart_enabled);
+
+    // Put the check mark back in line with the actual state
+    if let Err(e) = redraw_tray(app) {
+        eprintln!("Failed to update tray status: {}", e);
+    }
+}
+
+// Re-reads whether the app starts at login, since it can also be changed in
+// the system's startup settings. Returns whether it changed.
+fn refresh_autostart<R: Runtime>(app: &AppHandle<R>) -> bool {
+    let enabled = match autostart::is_enabled(app) {
+        Ok(enabled) => enabled,
+        Err(e) => {
+            log_error(&format!("Failed to read autostart state: {}", e));
+            return false;
+        }
+    };
+    let state = app.state::<AppState>();
+    let mut autostart_enabled = state.autostart_enabled.lock().unwrap();
+    let changed = *autostart_enabled != enabled;
+    *autostart_enabled = enabled;
+    changed
 }
 
 async fn battery_monitor_task<R: Runtime>(
     app: AppHandle<R>,
-    _mouse_battery: Arc<Mutex<MouseBattery>>,
+    mut settings_rx: watch::Receiver<Settings>,
+    mut theme_rx: watch::Receiver<Theme>,
+    mut refresh_rx: mpsc::Receiver<()>,
 ) {
-    let mut interval = interval(Duration::from_secs(30));
+    let mut period = settings_rx.borrow().polling_interval();
+    let mut interval = interval(period);
 
     loop {
-        interval.tick().await;
+        // Settings and theme changes only need a redraw; new device filters
+        // and calibration apply from the next poll
+        let poll = tokio::select! {
+            _ = interval.tick() => true,
+            changed = settings_rx.changed() => {
+                if changed.is_err() {
+                    break;
+                }
 
-        if let Err(e) = update_tray_status(&app) {
+                let settings = settings_rx.borrow_and_update().clone();
+                apply_settings(&app.state::<AppState>(), &settings);
+                load_icon_pack(&app, &settings.icon);
+                if let Some(tray) = app.try_state::<tauri::tray::TrayIcon>() {
+                    let menu_on_left_click = settings.tray.left_click == ClickAction::Menu;
+                    if let Err(e) = tray.set_show_menu_on_left_click(menu_on_left_click) {
+                        eprintln!("Failed to update tray click behaviour: {}", e);
+                    }
+                }
+                if settings.polling_interval() != period {
+                    period = settings.polling_interval();
+                    interval = tokio::time::interval(period);
+                    interval.reset();
+                }
+                false
+            }
+            changed = theme_rx.changed() => {
+                if changed.is_err() {
+                    break;
+                }
+                false
+            }
+            // Refresh() over D-Bus or the HTTP API, debounced together with
+            // refresh clicks
+            Some(()) = refresh_rx.recv() => app
+                .state::<AppState>()
+                .clicks
+                .lock()
+                .unwrap()
+                .should_run(ClickAction::Refresh, Instant::now()),
+        };
+
+        // While paused, only the countdown in the menu is kept current
+        let result = if poll && paused_for(&app.state::<AppState>()).is_none() {
+            update_tray_status(&app)
+        } else {
+            redraw_tray(&app)
+        };
+        if let Err(e) = result {
             eprintln!("Failed to update tray status: {}", e);
         }
     }
 }
+
+// Steps icon animations while the icon shows one: charging, or the blink
+// at critical level. Otherwise it only waits for new readings or settings.
+// Only the icon is redrawn from the latest reading; the mouse is not polled.
+async fn animation_task<R: Runtime>(app: AppHandle<R>) {
+    let (mut devices, mut settings_rx) = {
+        let state = app.state::<AppState>();
+        (state.devices.subscribe(), state.settings.subscribe())
+    };
+    let mut interval = interval(ANIMATION_FRAME);
+    // Resume at the frame rate after a pause rather than catching up
+    interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+    loop {
+        let animated = {
+            let settings = settings_rx.borrow_and_update();
+            devices.borrow_and_update().first().is_some_and(|reading| {
+                icon::is_animated(
+                    reading,
+                    &settings.icon,
+                    settings.notifications.critical_threshold,
+                )
+            })
+        };
+
+        tokio::select! {
+            _ = interval.tick(), if animated => draw_animation_frame(&app),
+            changed = devices.changed() => {
+                if changed.is_err() {
+                    return;
+                }
+            }
+            changed = settings_rx.changed() => {
+                if changed.is_err() {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+fn draw_animation_frame<R: Runtime>(app: &AppHandle<R>) {
+    let state = app.state::<AppState>();
+    *state.animation_tick.lock().unwrap() += 1;
+    let Some(reading) = state.last_reading.lock().unwrap().clone() else {
+        return;
+    };
+    let settings = state.settings.borrow().clone();
+
+    if let Some(tray) = app.try_state::<tauri::tray::TrayIcon>() {
+        match create_tray_icon(app, &reading, &settings) {
+            Ok(Some(icon)) => {
+                if let Err(e) = tray.set_icon(Some(icon)) {
+                    eprintln!("Failed to update tray icon: {}", e);
+                }
+            }
+            Ok(None) => {}
+            Err(e) => eprintln!("Failed to render tray icon: {}", e),
+        }
+    }
+}
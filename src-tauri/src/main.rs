@@ -24,6 +24,10 @@ fn log_startup() {
 }
 
 fn main() {
+    if let Some(code) = model_d2_pro_battery_lib::run_cli() {
+        std::process::exit(code);
+    }
+
     log_startup();
     model_d2_pro_battery_lib::run()
 }
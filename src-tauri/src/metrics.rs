@@ -0,0 +1,330 @@
+use crate::last_known::BatteryReading;
+use crate::mouse_battery::BatteryStatus;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+// Upper bounds of the poll duration buckets in seconds. Every read waits
+// 50 ms for the mouse to answer.
+const POLL_BUCKETS: [f64; 8] = [0.06, 0.08, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0];
+
+// Counted by the HID code for the whole process
+static SEND_ERRORS: AtomicU64 = AtomicU64::new(0);
+static RECEIVE_ERRORS: AtomicU64 = AtomicU64::new(0);
+static OPEN_FAILURES: AtomicU64 = AtomicU64::new(0);
+// Per bucket, not cumulative; the last one is +Inf
+static POLL_COUNTS: [AtomicU64; POLL_BUCKETS.len() + 1] =
+    [const { AtomicU64::new(0) }; POLL_BUCKETS.len() + 1];
+static POLL_MICROS: AtomicU64 = AtomicU64::new(0);
+
+// Devices seen since start, by ID, with their model, so a disconnected
+// mouse is reported as connected 0 instead of vanishing
+static SEEN: Mutex<BTreeMap<String, &'static str>> = Mutex::new(BTreeMap::new());
+
+// Which half of a feature report exchange failed
+pub enum HidError {
+    Send,
+    Receive,
+}
+
+pub fn record_hid_error(error: HidError) {
+    let counter = match error {
+        HidError::Send => &SEND_ERRORS,
+        HidError::Receive => &RECEIVE_ERRORS,
+    };
+    counter.fetch_add(1, Ordering::Relaxed);
+}
+
+pub fn record_open_failure() {
+    OPEN_FAILURES.fetch_add(1, Ordering::Relaxed);
+}
+
+// Time taken by one battery read of one mouse
+pub fn observe_poll(duration: Duration) {
+    POLL_COUNTS[poll_bucket(duration)].fetch_add(1, Ordering::Relaxed);
+    POLL_MICROS.fetch_add(duration.as_micros() as u64, Ordering::Relaxed);
+}
+
+fn poll_bucket(duration: Duration) -> usize {
+    let secs = duration.as_secs_f64();
+    POLL_BUCKETS
+        .iter()
+        .position(|&bound| secs <= bound)
+        .unwrap_or(POLL_BUCKETS.len())
+}
+
+// Remembers the connected mice on every poll, so one that goes away
+// between two scrapes is still reported
+pub fn record_devices(readings: &[BatteryReading]) {
+    let mut seen = SEEN.lock().unwrap();
+    for (id, reading) in connected(readings) {
+        if let Some(mouse_model) = reading.status.get_mouse_model() {
+            seen.insert(id, mouse_model.name());
+        }
+    }
+}
+
+// The connected mice by ID
+fn connected(readings: &[BatteryReading]) -> BTreeMap<String, &BatteryReading> {
+    readings
+        .iter()
+        .filter(|reading| !matches!(reading.status, BatteryStatus::NotFound))
+        .filter_map(|reading| Some((reading.device_id()?, reading)))
+        .collect()
+}
+
+// The readings and counters in the Prometheus text format
+pub fn render(readings: &[BatteryReading]) -> String {
+    let current = connected(readings);
+    // Normally recorded already by the poll that made the readings
+    let mut seen = SEEN.lock().unwrap().clone();
+    for (id, reading) in &current {
+        if let Some(mouse_model) = reading.status.get_mouse_model() {
+            seen.insert(id.clone(), mouse_model.name());
+        }
+    }
+
+    let mut out = String::new();
+    let labels =
+        |id: &str, model: &str| format!("device=\"{}\",model=\"{}\"", escape(id), escape(model));
+
+    header(
+        &mut out,
+        "glorious_battery_connected",
+        "gauge",
+        "Whether the mouse is connected",
+    );
+    for (id, model) in seen.iter() {
+        let connected = u8::from(current.contains_key(id));
+        let _ = writeln!(
+            out,
+            "glorious_battery_connected{{{}}} {}",
+            labels(id, model),
+            connected
+        );
+    }
+
+    header(
+        &mut out,
+        "glorious_battery_percent",
+        "gauge",
+        "Battery level as shown in the tray, the last known one while asleep",
+    );
+    for (id, reading) in &current {
+        if let Some(level) = reading.level() {
+            let _ = writeln!(
+                out,
+                "glorious_battery_percent{{{}}} {}",
+                labels(id, seen[id]),
+                level
+            );
+        }
+    }
+
+    header(
+        &mut out,
+        "glorious_battery_charging",
+        "gauge",
+        "Whether the mouse is charging, or full on the cable",
+    );
+    for (id, reading) in &current {
+        let charging = matches!(
+            reading.status,
+            BatteryStatus::Charging { .. } | BatteryStatus::FullyCharged { .. }
+        );
+        let _ = writeln!(
+            out,
+            "glorious_battery_charging{{{}}} {}",
+            labels(id, seen[id]),
+            u8::from(charging)
+        );
+    }
+
+    header(
+        &mut out,
+        "glorious_battery_asleep",
+        "gauge",
+        "Whether the mouse is asleep or waking up",
+    );
+    for (id, reading) in &current {
+        let asleep = matches!(
+            reading.status,
+            BatteryStatus::Asleep { .. } | BatteryStatus::WakingUp { .. }
+        );
+        let _ = writeln!(
+            out,
+            "glorious_battery_asleep{{{}}} {}",
+            labels(id, seen[id]),
+            u8::from(asleep)
+        );
+    }
+
+    header(
+        &mut out,
+        "glorious_battery_firmware_info",
+        "gauge",
+        "Firmware version of the mouse, once it has been read",
+    );
+    for (id, reading) in &current {
+        if let Some(firmware) = &reading.firmware {
+            let _ = writeln!(
+                out,
+                "glorious_battery_firmware_info{{{},version=\"{}\"}} 1",
+                labels(id, seen[id]),
+                escape(firmware)
+            );
+        }
+    }
+
+    header(
+        &mut out,
+        "glorious_battery_hid_errors_total",
+        "counter",
+        "Feature reports that failed while reading the battery",
+    );
+    for (direction, counter) in [("send", &SEND_ERRORS), ("receive", &RECEIVE_ERRORS)] {
+        let _ = writeln!(
+            out,
+            "glorious_battery_hid_errors_total{{direction=\"{}\"}} {}",
+            direction,
+            counter.load(Ordering::Relaxed)
+        );
+    }
+
+    header(
+        &mut out,
+        "glorious_battery_hid_open_failures_total",
+        "counter",
+        "Mice that were found but could not be opened",
+    );
+    let _ = writeln!(
+        out,
+        "glorious_battery_hid_open_failures_total {}",
+        OPEN_FAILURES.load(Ordering::Relaxed)
+    );
+
+    header(
+        &mut out,
+        "glorious_battery_poll_duration_seconds",
+        "histogram",
+        "Time taken to read the battery of one mouse",
+    );
+    let counts = POLL_COUNTS
+        .iter()
+        .map(|bucket| bucket.load(Ordering::Relaxed))
+        .collect::<Vec<_>>();
+    let sum = POLL_MICROS.load(Ordering::Relaxed) as f64 / 1e6;
+    write_histogram(
+        &mut out,
+        "glorious_battery_poll_duration_seconds",
+        &counts,
+        sum,
+    );
+
+    out
+}
+
+// Bucket lines with cumulative counts from the per-bucket `counts`, then
+// the sum and the total count
+fn write_histogram(out: &mut String, name: &str, counts: &[u64], sum: f64) {
+    let mut count = 0;
+    for (i, bucket) in counts.iter().enumerate() {
+        count += bucket;
+        let bound = POLL_BUCKETS
+            .get(i)
+            .map(|bound| bound.to_string())
+            .unwrap_or_else(|| "+Inf".to_string());
+        let _ = writeln!(out, "{}_bucket{{le=\"{}\"}} {}", name, bound, count);
+    }
+    let _ = writeln!(out, "{}_sum {}", name, sum);
+    let _ = writeln!(out, "{}_count {}", name, count);
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+// Label values escape backslashes, quotes and newlines
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mouse_battery::MouseModel;
+
+    fn reading(product_id: u16, firmware: Option<&str>) -> BatteryReading {
+        BatteryReading {
+            status: BatteryStatus::Normal {
+                percentage: 62,
+                mouse_model: MouseModel::from_product_id(product_id),
+            },
+            last_known: None,
+            remaining: None,
+            firmware: firmware.map(str::to_string),
+            raw_percentage: None,
+        }
+    }
+
+    #[test]
+    fn histogram_counts_are_cumulative() {
+        assert_eq!(poll_bucket(Duration::from_millis(55)), 0);
+        assert_eq!(poll_bucket(Duration::from_millis(60)), 0);
+        assert_eq!(poll_bucket(Duration::from_millis(70)), 1);
+        assert_eq!(poll_bucket(Duration::from_secs(10)), POLL_BUCKETS.len());
+
+        let mut out = String::new();
+        write_histogram(&mut out, "poll", &[1, 0, 2, 0, 0, 0, 0, 0, 3], 1.5);
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(
+            lines,
+            [
+                "poll_bucket{le=\"0.06\"} 1",
+                "poll_bucket{le=\"0.08\"} 1",
+                "poll_bucket{le=\"0.1\"} 3",
+                "poll_bucket{le=\"0.25\"} 3",
+                "poll_bucket{le=\"0.5\"} 3",
+                "poll_bucket{le=\"1\"} 3",
+                "poll_bucket{le=\"2.5\"} 3",
+                "poll_bucket{le=\"5\"} 3",
+                "poll_bucket{le=\"+Inf\"} 6",
+                "poll_sum 1.5",
+                "poll_count 6",
+            ]
+        );
+    }
+
+    #[test]
+    fn escapes_label_values() {
+        assert_eq!(escape("a\"b\\c\nd"), "a\\\"b\\\\c\\nd");
+
+        let out = render(&[reading(0x2034, Some("1.0 \"beta\"\n"))]);
+        let model = escape(MouseModel::from_product_id(0x2034).name());
+        let line = format!(
+            "glorious_battery_firmware_info{{device=\"2034\",model=\"{}\",version=\"1.0 \\\"beta\\\"\\n\"}} 1",
+            model
+        );
+        assert!(out.lines().any(|l| l == line), "{}", out);
+    }
+
+    #[test]
+    fn reports_mice_seen_while_polling() {
+        // Recorded by the poll, then gone before the scrape
+        record_devices(&[reading(0x2022, None)]);
+        let out = render(&[]);
+        let model = escape(MouseModel::from_product_id(0x2022).name());
+        let line = format!(
+            "glorious_battery_connected{{device=\"2022\",model=\"{}\"}} 0",
+            model
+        );
+        assert!(out.lines().any(|l| l == line), "{}", out);
+        assert!(!out.contains("glorious_battery_percent{device=\"2022\""));
+    }
+}
@@ -0,0 +1,169 @@
+use crate::alerts::{self, AlertTracker};
+use crate::estimate::RemainingEstimator;
+use crate::history;
+use crate::last_known::{BatteryReading, LastKnownCache};
+use crate::log_error;
+use crate::metrics;
+use crate::mouse_battery::{BatteryStatus, MouseBattery, MouseModel};
+use crate::settings::Settings;
+use crate::smoothing::BatteryFilter;
+use crate::template;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+// How long to wait before asking a mouse for its firmware version again
+// after it didn't answer
+const FIRMWARE_RETRY: Duration = Duration::from_secs(10 * 60);
+
+// Polls the mice and turns their raw status into readings. Shared by the
+// tray app and the headless daemon.
+pub struct Monitor {
+    pub mouse_battery: MouseBattery,
+    last_known: LastKnownCache,
+    battery_filter: BatteryFilter,
+    estimator: RemainingEstimator,
+    // Firmware versions per device, read once when something shows them
+    firmware: HashMap<MouseModel, String>,
+    // When reading a device's firmware version last failed
+    firmware_failed: HashMap<MouseModel, Instant>,
+    alerts: AlertTracker,
+    // Skips the history, notifications and hooks
+    read_only: bool,
+}
+
+impl Monitor {
+    pub fn new(mouse_battery: MouseBattery, settings: &Settings) -> Self {
+        let mut monitor = Self {
+            mouse_battery,
+            last_known: LastKnownCache::new(),
+            battery_filter: BatteryFilter::new(settings.polling.filter),
+            estimator: RemainingEstimator::new(),
+            firmware: HashMap::new(),
+            firmware_failed: HashMap::new(),
+            alerts: AlertTracker::new(),
+            read_only: false,
+        };
+        monitor.apply_settings(settings);
+        monitor
+    }
+
+    // A monitor that only reads the mice, for status bars
+    pub fn read_only(mouse_battery: MouseBattery, settings: &Settings) -> Self {
+        Self {
+            read_only: true,
+            ..Self::new(mouse_battery, settings)
+        }
+    }
+
+    // Device filter, calibration and smoothing from the settings
+    pub fn apply_settings(&mut self, settings: &Settings) {
+        self.mouse_battery.set_device_filter(
+            settings.devices.included_ids(),
+            settings.devices.excluded_ids(),
+        );
+        self.mouse_battery.set_calibration_overrides(
+            settings
+                .devices
+                .calibration_overrides()
+                .into_iter()
+                .collect(),
+        );
+        self.battery_filter.set_config(settings.polling.filter);
+    }
+
+    // Reads every connected mouse. The first reading is the one shown in
+    // the icon; a single NotFound reading stands in when no mouse is
+    // connected.
+    pub fn read_all(&mut self, settings: &Settings) -> Vec<BatteryReading> {
+        let mut statuses = self.mouse_battery.get_all_battery_status_with_raw();
+        if statuses.is_empty() {
+            statuses.push((BatteryStatus::NotFound, None));
+        }
+
+        let readings: Vec<BatteryReading> = statuses
+            .into_iter()
+            .map(|(raw_status, raw_battery)| self.process(settings, raw_status, raw_battery))
+            .collect();
+        metrics::record_devices(&readings);
+        readings
+    }
+
+    // Runs a device's raw status through history, smoothing, the last-known
+    // cache, the estimator and alerts; the history and alerts only when not
+    // read-only
+    fn process(
+        &mut self,
+        settings: &Settings,
+        raw_status: BatteryStatus,
+        raw_battery: Option<u8>,
+    ) -> BatteryReading {
+        if settings.history.enabled && !self.read_only {
+            if let Err(e) = history::record(&raw_status, raw_battery) {
+                log_error(&format!("Failed to record battery history: {}", e));
+            }
+        }
+        let battery_status = self.battery_filter.apply(raw_status);
+        let mut reading = self.last_known.observe(battery_status);
+        if !settings.icon.show_last_known {
+            reading.last_known = None;
+        }
+        reading.remaining = self.estimator.observe(&reading.status);
+        if shows_firmware(settings) {
+            reading.firmware = self.firmware_version(&reading.status);
+        }
+        reading.raw_percentage = raw_battery;
+
+        if !self.read_only {
+            for event in self.alerts.observe(&reading, settings) {
+                alerts::dispatch(event, &reading, settings);
+            }
+        }
+
+        reading
+    }
+
+    // Firmware version of the reading's device, asking the mouse only the
+    // first time it is seen awake, and after a failed read only once
+    // FIRMWARE_RETRY has passed
+    fn firmware_version(&mut self, status: &BatteryStatus) -> Option<String> {
+        let mouse_model = status.get_mouse_model()?;
+        if let Some(version) = self.firmware.get(&mouse_model) {
+            return Some(version.clone());
+        }
+        status.percentage()?;
+        if self
+            .firmware_failed
+            .get(&mouse_model)
+            .is_some_and(|failed| failed.elapsed() < FIRMWARE_RETRY)
+        {
+            return None;
+        }
+
+        match self.mouse_battery.get_firmware_version_for(mouse_model) {
+            Some(version) => {
+                self.firmware_failed.remove(&mouse_model);
+                self.firmware.insert(mouse_model, version.clone());
+                Some(version)
+            }
+            None => {
+                self.firmware_failed.insert(mouse_model, Instant::now());
+                None
+            }
+        }
+    }
+}
+
+// Whether anything shows the firmware version: a template, the device
+// submenus or the HTTP API. Otherwise the mouse isn't asked for it.
+fn shows_firmware(settings: &Settings) -> bool {
+    let templates = [
+        &settings.icon.text,
+        &settings.icon.tooltip,
+        &settings.menu.status_text,
+    ];
+    templates
+        .iter()
+        .any(|t| t.as_deref().is_some_and(|t| template::uses(t, "firmware")))
+        || settings.menu.show_devices
+        || settings.api.enabled
+}
@@ -1,7 +1,11 @@
+use crate::calibration;
+use crate::i18n;
+use crate::metrics::{self, HidError};
 use hidapi::{HidApi, HidDevice};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 // Centralized mouse configuration - add new mice here
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -9,6 +13,12 @@ pub struct MouseConfig {
     pub product_id: u16,
     pub name: &'static str,
     pub is_wired: bool,
+    // Letter shown in the tray icon's model badge
+    pub badge: char,
+    // (reported, percent) points mapping the reported value to a corrected
+    // percentage, see calibration::apply. Empty means no correction, which
+    // is the case for every mouse until a measured table is contributed.
+    pub calibration: &'static [(u8, u8)],
 }
 
 // All supported Glorious mice - add new entries here to extend support
@@ -17,21 +27,29 @@ pub const SUPPORTED_MICE: &[MouseConfig] = &[
         product_id: 0x2011,
         name: "Model O Wired",
         is_wired: true,
+        badge: 'O',
+        calibration: &[],
     },
     MouseConfig {
         product_id: 0x2022,
         name: "Model O Wireless",
         is_wired: false,
+        badge: 'O',
+        calibration: &[],
     },
     MouseConfig {
         product_id: 0x2027,
         name: "Model O PRO Wireless",
         is_wired: false,
+        badge: 'O',
+        calibration: &[],
     },
     MouseConfig {
         product_id: 0x2034,
         name: "Model D 2 PRO Wireless",
         is_wired: false,
+        badge: 'D',
+        calibration: &[],
     },
 ];
 
@@ -75,6 +93,8 @@ pub enum BatteryStatus {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MouseInfo {
     pub battery_status: BatteryStatus,
+    // Unfiltered percentage as reported by the mouse, for diagnostics
+    pub raw_percentage: Option<u8>,
     pub firmware_version: Option<String>,
 }
 
@@ -102,6 +122,18 @@ impl MouseModel {
         self.config().map(|c| c.is_wired).unwrap_or(false)
     }
 
+    pub fn product_id(&self) -> Option<u16> {
+        self.config().map(|c| c.product_id)
+    }
+
+    pub fn badge(&self) -> Option<char> {
+        self.config().map(|c| c.badge)
+    }
+
+    pub fn calibration(&self) -> &'static [(u8, u8)] {
+        self.config().map(|c| c.calibration).unwrap_or(&[])
+    }
+
     fn config(&self) -> Option<&'static MouseConfig> {
         self.config_index.map(|i| &SUPPORTED_MICE[i])
     }
@@ -136,40 +168,85 @@ impl<'de> Deserialize<'de> for MouseModel {
 
 pub struct MouseBattery {
     hid_api: HidApi,
+    // Product IDs to monitor (empty means all) and to ignore
+    include: Vec<u16>,
+    exclude: Vec<u16>,
+    // Calibration tables replacing the built-in ones, by product ID
+    calibration_overrides: HashMap<u16, Vec<(u8, u8)>>,
 }
 
 impl MouseBattery {
     pub fn new() -> Result<Self, String> {
         let hid_api = HidApi::new().map_err(|e| format!("Failed to initialize HID API: {}", e))?;
-        Ok(Self { hid_api })
+        Ok(Self {
+            hid_api,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            calibration_overrides: HashMap::new(),
+        })
+    }
+
+    pub fn set_device_filter(&mut self, include: Vec<u16>, exclude: Vec<u16>) {
+        self.include = include;
+        self.exclude = exclude;
+    }
+
+    pub fn set_calibration_overrides(&mut self, overrides: HashMap<u16, Vec<(u8, u8)>>) {
+        self.calibration_overrides = overrides;
+    }
+
+    fn is_monitored(&self, product_id: u16) -> bool {
+        (self.include.is_empty() || self.include.contains(&product_id))
+            && !self.exclude.contains(&product_id)
+    }
+
+    fn calibration_for(&self, mouse_model: MouseModel) -> &[(u8, u8)] {
+        mouse_model
+            .product_id()
+            .and_then(|pid| self.calibration_overrides.get(&pid))
+            .map(|table| table.as_slice())
+            .unwrap_or(mouse_model.calibration())
     }
 
     pub fn find_device(&self) -> Option<hidapi::DeviceInfo> {
+        self.find_devices().into_iter().next()
+    }
+
+    // Every connected, monitored mouse, wired ones first
+    pub fn find_devices(&self) -> Vec<hidapi::DeviceInfo> {
         let supported_pids = MouseConfig::all_product_ids();
 
-        self.hid_api
+        let mut devices: Vec<hidapi::DeviceInfo> = self
+            .hid_api
             .device_list()
             .filter(|d| {
                 // Glorious' vendor id
                 d.vendor_id() == 0x258A &&
                 // Check if product ID is in our supported list
                 supported_pids.contains(&d.product_id()) &&
+                // Respect the configured device filters
+                self.is_monitored(d.product_id()) &&
                 // Feature report interface
                 d.interface_number() == 0x02
             })
-            // Prefer wired mice (lower product ID typically means wired)
-            .min_by(|a, b| {
-                let a_wired = MouseConfig::from_product_id(a.product_id())
-                    .map(|c| if c.is_wired { 0 } else { 1 })
-                    .unwrap_or(2);
-                let b_wired = MouseConfig::from_product_id(b.product_id())
-                    .map(|c| if c.is_wired { 0 } else { 1 })
-                    .unwrap_or(2);
-                a_wired
-                    .cmp(&b_wired)
-                    .then_with(|| a.product_id().cmp(&b.product_id()))
-            })
-            .map(|d| d.clone())
+            .cloned()
+            .collect();
+
+        // Prefer wired mice (lower product ID typically means wired)
+        devices.sort_by(|a, b| {
+            let a_wired = MouseConfig::from_product_id(a.product_id())
+                .map(|c| if c.is_wired { 0 } else { 1 })
+                .unwrap_or(2);
+            let b_wired = MouseConfig::from_product_id(b.product_id())
+                .map(|c| if c.is_wired { 0 } else { 1 })
+                .unwrap_or(2);
+            a_wired
+                .cmp(&b_wired)
+                .then_with(|| a.product_id().cmp(&b.product_id()))
+        });
+        // One entry per model, even if the OS lists the interface twice
+        devices.dedup_by_key(|d| d.product_id());
+        devices
     }
 
     pub fn get_detected_model(&self) -> Option<MouseModel> {
@@ -178,28 +255,67 @@ impl MouseBattery {
     }
 
     pub fn get_battery_status(&self) -> BatteryStatus {
+        self.get_battery_status_with_raw().0
+    }
+
+    // Battery status along with the uncalibrated value reported by the mouse
+    pub fn get_battery_status_with_raw(&self) -> (BatteryStatus, Option<u8>) {
         let device_info = match self.find_device() {
             Some(info) => info,
-            None => return BatteryStatus::NotFound,
+            None => return (BatteryStatus::NotFound, None),
         };
 
         let mouse_model = MouseModel::from_product_id(device_info.product_id());
         let wired = mouse_model.is_wired();
 
-        let device = match device_info.open_device(&self.hid_api) {
-            Ok(dev) => dev,
-            Err(_) => return BatteryStatus::NotFound,
+        let device = match self.open(&device_info) {
+            Some(dev) => dev,
+            None => return (BatteryStatus::NotFound, None),
         };
 
         self.read_battery_status(&device, wired, mouse_model)
     }
 
+    // Status of every connected mouse, in find_devices order. Mice that
+    // can't be opened are left out.
+    pub fn get_all_battery_status_with_raw(&self) -> Vec<(BatteryStatus, Option<u8>)> {
+        self.find_devices()
+            .iter()
+            .filter_map(|device_info| {
+                let mouse_model = MouseModel::from_product_id(device_info.product_id());
+                let device = self.open(device_info)?;
+                Some(self.read_battery_status(&device, mouse_model.is_wired(), mouse_model))
+            })
+            .collect()
+    }
+
+    // Opens a found mouse, counting failures for the metrics
+    fn open(&self, device_info: &hidapi::DeviceInfo) -> Option<HidDevice> {
+        let device = device_info.open_device(&self.hid_api).ok();
+        if device.is_none() {
+            metrics::record_open_failure();
+        }
+        device
+    }
+
     fn read_battery_status(
         &self,
         device: &HidDevice,
         wired: bool,
         mouse_model: MouseModel,
-    ) -> BatteryStatus {
+    ) -> (BatteryStatus, Option<u8>) {
+        let started = Instant::now();
+        let result = self.query_battery_status(device, wired, mouse_model);
+        metrics::observe_poll(started.elapsed());
+        result
+    }
+
+    fn query_battery_status(
+        &self,
+        device: &HidDevice,
+        wired: bool,
+        mouse_model: MouseModel,
+    ) -> (BatteryStatus, Option<u8>) {
         let mut bfr_w = [0u8; 65];
 
         bfr_w[3] = 0x02;
@@ -207,11 +323,13 @@ impl MouseBattery {
         bfr_w[6] = 0x83;
 
         if device.send_feature_report(&bfr_w).is_err() {
-            return BatteryStatus::Unknown {
+            metrics::record_hid_error(HidError::Send);
+            let status = BatteryStatus::Unknown {
                 raw_status: 0,
                 raw_battery: 0,
                 mouse_model,
             };
+            return (status, None);
         }
 
         thread::sleep(Duration::from_millis(50));
@@ -219,14 +337,17 @@ impl MouseBattery {
         let mut bfr_r = [0u8; 65];
 
         if device.get_feature_report(&mut bfr_r).is_err() {
-            return BatteryStatus::Unknown {
+            metrics::record_hid_error(HidError::Receive);
+            let status = BatteryStatus::Unknown {
                 raw_status: 0,
                 raw_battery: 0,
                 mouse_model,
             };
+            return (status, None);
         }
 
-        let mut percentage = bfr_r[8];
+        let raw_battery = bfr_r[8];
+        let mut percentage = calibration::apply(self.calibration_for(mouse_model), raw_battery);
 
         if percentage == 0 {
             percentage = 1;
@@ -238,7 +359,7 @@ impl MouseBattery {
 
         let status = if bfr_r[6] != 0x83 { Some(2) } else { status };
 
-        match (status, wired) {
+        let status = match (status, wired) {
             (Some(0), false) => BatteryStatus::Normal {
                 percentage,
                 mouse_model,
@@ -257,17 +378,32 @@ impl MouseBattery {
             (Some(3), _) => BatteryStatus::WakingUp { mouse_model },
             _ => BatteryStatus::Unknown {
                 raw_status: bfr_r[1],
-                raw_battery: bfr_r[8],
+                raw_battery,
                 mouse_model,
             },
-        }
+        };
+
+        (status, Some(raw_battery))
     }
 
     pub fn get_firmware_version(&self) -> Option<String> {
-        let device_info = self.find_device()?;
+        self.read_firmware_version(&self.find_device()?)
+    }
+
+    // Firmware version of a specific connected mouse
+    pub fn get_firmware_version_for(&self, mouse_model: MouseModel) -> Option<String> {
+        let product_id = mouse_model.product_id()?;
+        let device_info = self
+            .find_devices()
+            .into_iter()
+            .find(|d| d.product_id() == product_id)?;
+        self.read_firmware_version(&device_info)
+    }
+
+    fn read_firmware_version(&self, device_info: &hidapi::DeviceInfo) -> Option<String> {
         let mouse_model = MouseModel::from_product_id(device_info.product_id());
         let wired = mouse_model.is_wired();
-        let device = device_info.open_device(&self.hid_api).ok()?;
+        let device = self.open(device_info)?;
 
         let mut bfr_w = [0u8; 65];
 
@@ -293,11 +429,12 @@ impl MouseBattery {
     }
 
     pub fn get_mouse_info(&self) -> MouseInfo {
-        let battery_status = self.get_battery_status();
+        let (battery_status, raw_percentage) = self.get_battery_status_with_raw();
         let firmware_version = self.get_firmware_version();
 
         MouseInfo {
             battery_status,
+            raw_percentage,
             firmware_version,
         }
     }
@@ -329,6 +466,28 @@ impl BatteryStatus {
         }
     }
 
+    pub fn percentage(&self) -> Option<u8> {
+        match self {
+            BatteryStatus::Normal { percentage, .. } => Some(*percentage),
+            BatteryStatus::Charging { percentage, .. } => Some(*percentage),
+            BatteryStatus::FullyCharged { .. } => Some(100),
+            _ => None,
+        }
+    }
+
+    // Short machine-friendly name of the state, used by hooks and exports
+    pub fn state_name(&self) -> &'static str {
+        match self {
+            BatteryStatus::Normal { .. } => "discharging",
+            BatteryStatus::Charging { .. } => "charging",
+            BatteryStatus::FullyCharged { .. } => "full",
+            BatteryStatus::Asleep { .. } => "asleep",
+            BatteryStatus::WakingUp { .. } => "waking",
+            BatteryStatus::NotFound => "not_found",
+            BatteryStatus::Unknown { .. } => "unknown",
+        }
+    }
+
     pub fn get_mouse_model(&self) -> Option<MouseModel> {
         match self {
             BatteryStatus::Normal { mouse_model, .. } => Some(*mouse_model),
@@ -341,28 +500,37 @@ impl BatteryStatus {
         }
     }
 
+    // Mouse name for display, with a translated fallback
+    pub fn display_name(&self) -> String {
+        self.get_mouse_model()
+            .map(|m| m.name().to_string())
+            .unwrap_or_else(|| i18n::tr("mouse-generic"))
+    }
+
     pub fn get_tooltip(&self) -> String {
-        let mouse_name = self.get_mouse_model().map(|m| m.name()).unwrap_or("Mouse");
+        let name = self.display_name();
 
         match self {
-            BatteryStatus::Normal { percentage, .. } => {
-                format!("{}: {}%", mouse_name, percentage)
-            }
-            BatteryStatus::Charging { percentage, .. } => {
-                format!("{}: {}% (Charging)", mouse_name, percentage)
-            }
+            BatteryStatus::Normal { percentage, .. } => i18n::tr_with(
+                "status-normal",
+                &[("name", name.into()), ("percent", (*percentage).into())],
+            ),
+            BatteryStatus::Charging { percentage, .. } => i18n::tr_with(
+                "status-charging",
+                &[("name", name.into()), ("percent", (*percentage).into())],
+            ),
             BatteryStatus::FullyCharged { .. } => {
-                format!("{}: Fully Charged", mouse_name)
+                i18n::tr_with("status-full", &[("name", name.into())])
             }
             BatteryStatus::Asleep { .. } => {
-                format!("{}: Mouse is asleep", mouse_name)
+                i18n::tr_with("status-asleep", &[("name", name.into())])
             }
             BatteryStatus::WakingUp { .. } => {
-                format!("{}: Waking up...", mouse_name)
+                i18n::tr_with("status-waking", &[("name", name.into())])
             }
-            BatteryStatus::NotFound => "Mouse: Device not found".to_string(),
+            BatteryStatus::NotFound => i18n::tr("status-not-found"),
             BatteryStatus::Unknown { .. } => {
-                format!("{}: Unknown status", mouse_name)
+                i18n::tr_with("status-unknown", &[("name", name.into())])
             }
         }
     }
@@ -0,0 +1,13 @@
+use std::path::PathBuf;
+
+const APP_DIR: &str = "ModelD2ProBattery";
+
+// Directory for logs and the battery history, e.g. %LOCALAPPDATA%\ModelD2ProBattery
+pub fn log_dir() -> Option<PathBuf> {
+    dirs::data_local_dir().map(|dir| dir.join(APP_DIR))
+}
+
+// Directory for settings, e.g. ~/.config/ModelD2ProBattery on Linux
+pub fn config_dir() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join(APP_DIR))
+}
@@ -0,0 +1,734 @@
+use crate::calibration;
+use crate::clicks::ClickAction;
+use crate::i18n;
+use crate::icon::{IconStyle, ICON_SIZES};
+use crate::paths;
+use crate::smoothing::{FilterConfig, FilterKind};
+use crate::template;
+use crate::theme::ThemeSetting;
+use notify_debouncer_mini::{new_debouncer, notify::RecursiveMode, DebounceEventResult, Debouncer};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::sync::watch;
+
+const SETTINGS_FILE: &str = "settings.toml";
+
+// Upgrades a settings table by one schema version. MIGRATIONS[i] turns a
+// version i + 1 file into a version i + 2 file.
+type Migration = fn(&mut toml::Table) -> Result<(), String>;
+
+const MIGRATIONS: &[Migration] = &[drop_font_sizes];
+
+pub const SCHEMA_VERSION: u32 = MIGRATIONS.len() as u32 + 1;
+
+// v1 -> v2: text is fitted to the icon automatically, so the fixed font
+// sizes are gone
+fn drop_font_sizes(table: &mut toml::Table) -> Result<(), String> {
+    if let Some(toml::Value::Table(icon)) = table.get_mut("icon") {
+        for key in ["font_size_short", "font_size_medium", "font_size_long"] {
+            icon.remove(key);
+        }
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    pub schema_version: u32,
+    // Locale for menus and messages, e.g. "de", or "auto" for the system's
+    pub language: String,
+    pub polling: PollingSettings,
+    pub notifications: NotificationSettings,
+    pub icon: IconSettings,
+    pub menu: MenuSettings,
+    pub tray: TraySettings,
+    pub devices: DeviceSettings,
+    pub history: HistorySettings,
+    pub hooks: HookSettings,
+    pub api: ApiSettings,
+    pub dbus: DbusSettings,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PollingSettings {
+    pub interval_secs: u64,
+    pub filter: FilterConfig,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NotificationSettings {
+    pub enabled: bool,
+    pub low_threshold: u8,
+    pub critical_threshold: u8,
+    pub charging: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct IconSettings {
+    pub style: IconStyle,
+    // Levels at or below which the gauge turns amber and red
+    pub amber_threshold: u8,
+    pub red_threshold: u8,
+    // Draw the digits in the level colour instead of the theme colour
+    pub colored_text: bool,
+    // Overlay a lightning bolt while charging
+    pub charging_bolt: bool,
+    // Animate charging and the critical blink; off keeps the icon still
+    pub animate: bool,
+    // Blink at or below notifications.critical_threshold
+    pub blink_critical: bool,
+    // Corner badges showing cable or receiver, and the model letter
+    pub connection_badge: bool,
+    pub model_badge: bool,
+    // Icon size in physical pixels, or 0 to follow the display scale
+    pub size: u32,
+    // Panel colour scheme to draw for; auto follows the system
+    pub theme: ThemeSetting,
+    // Directory with a manifest.toml of custom icons, relative to the
+    // settings directory unless absolute
+    pub pack: Option<PathBuf>,
+    // Templates for the icon text and tooltip, e.g. "{percent}" and
+    // "{name}: {percent}% {state} ({remaining})". Unset uses the built-in
+    // text; the icon template is only used while a level is known.
+    pub text: Option<String>,
+    pub tooltip: Option<String>,
+    pub asleep_text: String,
+    pub waking_text: String,
+    pub not_found_text: String,
+    pub unknown_text: String,
+    // Show the last known level while the mouse is asleep
+    pub show_last_known: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MenuSettings {
+    pub show_status: bool,
+    // Template for the status line, like icon.tooltip
+    pub status_text: Option<String>,
+    // A submenu per connected mouse with its level, state and firmware
+    pub show_devices: bool,
+    // Pause monitoring, copy status, open log folder and history
+    pub show_quick_actions: bool,
+    pub show_refresh: bool,
+    pub show_firmware: bool,
+    pub show_autostart: bool,
+}
+
+// What clicking the tray icon does. Left click "menu" leaves it to the
+// system; Linux panels only ever open the menu.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TraySettings {
+    pub left_click: ClickAction,
+    pub double_click: ClickAction,
+    pub middle_click: ClickAction,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DeviceSettings {
+    // Product IDs (hex, e.g. "2034") to monitor; empty means all supported mice
+    pub include: Vec<String>,
+    // Product IDs to ignore
+    pub exclude: Vec<String>,
+    // Calibration tables overriding the built-in ones, keyed by product ID
+    pub calibration: BTreeMap<String, Vec<(u8, u8)>>,
+}
+
+// Logging of raw readings to battery_history.csv, the input of the
+// calibrate command
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HistorySettings {
+    pub enabled: bool,
+}
+
+// Commands run through the shell when the matching event happens. The
+// battery state is passed in MOUSE_NAME, BATTERY_PERCENT and BATTERY_STATE.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HookSettings {
+    pub on_change: Option<String>,
+    pub on_low: Option<String>,
+    pub on_critical: Option<String>,
+    pub on_charging_started: Option<String>,
+    pub on_charging_stopped: Option<String>,
+    // Run by the "run_hook" click action
+    pub on_click: Option<String>,
+}
+
+// Local HTTP API, bound to 127.0.0.1. With a token set, every request has
+// to send it as a bearer token or in ?token=.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ApiSettings {
+    pub enabled: bool,
+    pub port: u16,
+    pub token: Option<String>,
+    // Serve /metrics for Prometheus
+    pub metrics: bool,
+}
+
+// The session bus service on Linux. With upower set, each mouse object also
+// implements org.freedesktop.UPower.Device; upowerd doesn't pick these up,
+// so only clients that read our bus name see them.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DbusSettings {
+    pub upower: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            language: "auto".to_string(),
+            polling: PollingSettings::default(),
+            notifications: NotificationSettings::default(),
+            icon: IconSettings::default(),
+            menu: MenuSettings::default(),
+            tray: TraySettings::default(),
+            devices: DeviceSettings::default(),
+            history: HistorySettings::default(),
+            hooks: HookSettings::default(),
+            api: ApiSettings::default(),
+            dbus: DbusSettings::default(),
+        }
+    }
+}
+
+impl Default for PollingSettings {
+    fn default() -> Self {
+        Self {
+            interval_secs: 30,
+            filter: FilterConfig::default(),
+        }
+    }
+}
+
+impl Default for NotificationSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            low_threshold: 20,
+            critical_threshold: 10,
+            charging: false,
+        }
+    }
+}
+
+impl Default for IconSettings {
+    fn default() -> Self {
+        Self {
+            style: IconStyle::Text,
+            amber_threshold: 50,
+            red_threshold: 20,
+            colored_text: false,
+            charging_bolt: true,
+            animate: false,
+            blink_critical: false,
+            connection_badge: false,
+            model_badge: false,
+            size: 0,
+            theme: ThemeSetting::Auto,
+            pack: None,
+            text: None,
+            tooltip: None,
+            asleep_text: "ZZZ".to_string(),
+            waking_text: "...".to_string(),
+            not_found_text: "N/A".to_string(),
+            unknown_text: "???".to_string(),
+            show_last_known: true,
+        }
+    }
+}
+
+impl Default for MenuSettings {
+    fn default() -> Self {
+        Self {
+            show_status: true,
+            status_text: None,
+            show_devices: true,
+            show_quick_actions: true,
+            show_refresh: true,
+            show_firmware: true,
+            show_autostart: true,
+        }
+    }
+}
+
+impl Default for TraySettings {
+    fn default() -> Self {
+        Self {
+            left_click: ClickAction::Menu,
+            double_click: ClickAction::None,
+            middle_click: ClickAction::None,
+        }
+    }
+}
+
+impl Default for ApiSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: 7385,
+            token: None,
+            metrics: false,
+        }
+    }
+}
+
+impl DeviceSettings {
+    pub fn included_ids(&self) -> Vec<u16> {
+        self.include
+            .iter()
+            .filter_map(|id| parse_product_id(id))
+            .collect()
+    }
+
+    pub fn excluded_ids(&self) -> Vec<u16> {
+        self.exclude
+            .iter()
+            .filter_map(|id| parse_product_id(id))
+            .collect()
+    }
+
+    pub fn calibration_overrides(&self) -> Vec<(u16, Vec<(u8, u8)>)> {
+        self.calibration
+            .iter()
+            .filter_map(|(id, table)| parse_product_id(id).map(|pid| (pid, table.clone())))
+            .collect()
+    }
+}
+
+pub fn parse_product_id(value: &str) -> Option<u16> {
+    u16::from_str_radix(value.trim().trim_start_matches("0x"), 16).ok()
+}
+
+impl Settings {
+    pub fn polling_interval(&self) -> Duration {
+        Duration::from_secs(self.polling.interval_secs)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.language != "auto" && i18n::resolve(&self.language).is_none() {
+            return Err(format!(
+                "language must be \"auto\" or one of {} (got \"{}\")",
+                i18n::available().join(", "),
+                self.language
+            ));
+        }
+
+        let polling = &self.polling;
+        if !(5..=3600).contains(&polling.interval_secs) {
+            return Err(format!(
+                "polling.interval_secs must be between 5 and 3600 (got {})",
+                polling.interval_secs
+            ));
+        }
+        if polling.filter.kind != FilterKind::Off {
+            if !(1..=50).contains(&polling.filter.window) {
+                return Err(format!(
+                    "polling.filter.window must be between 1 and 50 (got {})",
+                    polling.filter.window
+                ));
+            }
+            if !(polling.filter.ema_alpha > 0.0 && polling.filter.ema_alpha <= 1.0) {
+                return Err(format!(
+                    "polling.filter.ema_alpha must be above 0 and at most 1 (got {})",
+                    polling.filter.ema_alpha
+                ));
+            }
+            if polling.filter.hysteresis > 20 {
+                return Err(format!(
+                    "polling.filter.hysteresis must be at most 20 (got {})",
+                    polling.filter.hysteresis
+                ));
+            }
+        }
+
+        let notifications = &self.notifications;
+        if notifications.low_threshold > 100 {
+            return Err(format!(
+                "notifications.low_threshold must be at most 100 (got {})",
+                notifications.low_threshold
+            ));
+        }
+        if notifications.critical_threshold > notifications.low_threshold {
+            return Err(format!(
+                "notifications.critical_threshold ({}) must not be above low_threshold ({})",
+                notifications.critical_threshold, notifications.low_threshold
+            ));
+        }
+
+        let icon = &self.icon;
+        if icon.amber_threshold > 100 || icon.red_threshold > icon.amber_threshold {
+            return Err(format!(
+                "icon.red_threshold ({}) must not be above amber_threshold ({}), which must be at most 100",
+                icon.red_threshold, icon.amber_threshold
+            ));
+        }
+        if icon.size != 0 && !ICON_SIZES.contains(&icon.size) {
+            return Err(format!(
+                "icon.size must be 0 (automatic) or one of {:?} (got {})",
+                ICON_SIZES, icon.size
+            ));
+        }
+        for (name, text) in [
+            ("asleep_text", &icon.asleep_text),
+            ("waking_text", &icon.waking_text),
+            ("not_found_text", &icon.not_found_text),
+            ("unknown_text", &icon.unknown_text),
+        ] {
+            let len = text.chars().count();
+            if len == 0 || len > 4 {
+                return Err(format!(
+                    "icon.{} must be 1 to 4 characters (got \"{}\")",
+                    name, text
+                ));
+            }
+        }
+
+        for (name, text) in [
+            ("icon.text", &icon.text),
+            ("icon.tooltip", &icon.tooltip),
+            ("menu.status_text", &self.menu.status_text),
+        ] {
+            if let Some(text) = text {
+                template::validate(text).map_err(|e| format!("{}: {}", name, e))?;
+            }
+        }
+
+        let tray = &self.tray;
+        for (name, action) in [
+            ("left_click", tray.left_click),
+            ("double_click", tray.double_click),
+            ("middle_click", tray.middle_click),
+        ] {
+            if action == ClickAction::Menu && name != "left_click" {
+                return Err(format!(
+                    "tray.{}: the menu can only be opened by left_click",
+                    name
+                ));
+            }
+            if action == ClickAction::RunHook && self.hooks.on_click.is_none() {
+                return Err(format!(
+                    "tray.{} is {} but hooks.on_click is not set",
+                    name,
+                    action.name()
+                ));
+            }
+        }
+
+        let api = &self.api;
+        if api.port < 1024 {
+            return Err(format!(
+                "api.port must be between 1024 and 65535 (got {})",
+                api.port
+            ));
+        }
+        if let Some(token) = &api.token {
+            let url_safe =
+                |c: char| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | '~');
+            if token.len() < 16 || !token.chars().all(url_safe) {
+                return Err(
+                    "api.token must be at least 16 letters, digits, -, _, . or ~".to_string(),
+                );
+            }
+        }
+
+        let devices = &self.devices;
+        for (field, ids) in [("include", &devices.include), ("exclude", &devices.exclude)] {
+            if let Some(id) = ids.iter().find(|id| parse_product_id(id).is_none()) {
+                return Err(format!(
+                    "devices.{} contains \"{}\", which is not a hex product ID",
+                    field, id
+                ));
+            }
+        }
+        for (id, table) in &devices.calibration {
+            if parse_product_id(id).is_none() {
+                return Err(format!(
+                    "devices.calibration key \"{}\" is not a hex product ID",
+                    id
+                ));
+            }
+            calibration::validate(table)
+                .map_err(|e| format!("devices.calibration.{}: {}", id, e))?;
+        }
+
+        let hooks = &self.hooks;
+        for (name, hook) in [
+            ("on_change", &hooks.on_change),
+            ("on_low", &hooks.on_low),
+            ("on_critical", &hooks.on_critical),
+            ("on_charging_started", &hooks.on_charging_started),
+            ("on_charging_stopped", &hooks.on_charging_stopped),
+            ("on_click", &hooks.on_click),
+        ] {
+            if hook.as_deref().is_some_and(|cmd| cmd.trim().is_empty()) {
+                return Err(format!("hooks.{} must not be empty", name));
+            }
+        }
+
+        Ok(())
+    }
+
+    // Parses and validates a settings file, upgrading older schema versions.
+    // Returns whether a migration was applied.
+    pub fn parse(content: &str) -> Result<(Self, bool), String> {
+        let mut table: toml::Table = content.parse().map_err(|e| format!("{}", e))?;
+
+        let version = match table.get("schema_version") {
+            None => 1,
+            Some(toml::Value::Integer(v)) if *v >= 1 => *v as u32,
+            Some(other) => {
+                return Err(format!(
+                    "schema_version must be a positive integer (got {})",
+                    other
+                ))
+            }
+        };
+
+        if version > SCHEMA_VERSION {
+            return Err(format!(
+                "schema_version {} is newer than this app supports ({})",
+                version, SCHEMA_VERSION
+            ));
+        }
+
+        // Deserialize the original text when possible so errors point at
+        // the user's lines
+        let migrated = version < SCHEMA_VERSION;
+        let settings: Settings = if migrated {
+            for (i, migration) in MIGRATIONS.iter().enumerate().skip(version as usize - 1) {
+                migration(&mut table)
+                    .map_err(|e| format!("failed to migrate from schema {}: {}", i + 1, e))?;
+            }
+            table.insert(
+                "schema_version".to_string(),
+                toml::Value::Integer(SCHEMA_VERSION as i64),
+            );
+            let upgraded = toml::to_string(&table).map_err(|e| e.to_string())?;
+            toml::from_str(&upgraded).map_err(|e| e.to_string())?
+        } else {
+            toml::from_str(content).map_err(|e| e.to_string())?
+        };
+        settings.validate()?;
+
+        Ok((settings, migrated))
+    }
+
+    pub fn to_toml(&self) -> Result<String, String> {
+        toml::to_string_pretty(self).map_err(|e| format!("Failed to serialize settings: {}", e))
+    }
+}
+
+pub fn settings_path() -> Option<PathBuf> {
+    paths::config_dir().map(|dir| dir.join(SETTINGS_FILE))
+}
+
+// Reads a settings file, upgrading it in place when it uses an older
+// schema. Also returns the upgraded text when it was written back.
+fn read_file(path: &Path) -> Result<(Settings, Option<String>), String> {
+    let (settings, migrated) = parse_file(path)?;
+    if !migrated {
+        return Ok((settings, None));
+    }
+    // Keep the original next to the upgraded file
+    let _ = fs::copy(path, path.with_extension("toml.bak"));
+    save_to(path, &settings)?;
+    let upgraded = settings.to_toml()?;
+    Ok((settings, Some(upgraded)))
+}
+
+fn parse_file(path: &Path) -> Result<(Settings, bool), String> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    Settings::parse(&content).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+fn save_to(path: &Path, settings: &Settings) -> Result<(), String> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("Failed to create config directory: {}", e))?;
+    }
+    fs::write(path, settings.to_toml()?)
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+// Loads the settings file, writing the defaults if it doesn't exist yet
+pub fn load() -> Result<Settings, String> {
+    let path = settings_path().ok_or("No config directory available")?;
+    if !path.exists() {
+        let settings = Settings::default();
+        save_to(&path, &settings)?;
+        return Ok(settings);
+    }
+    read_file(&path).map(|(settings, _)| settings)
+}
+
+// Loads the settings for commands that only read them: the defaults when
+// there is no file, and an older schema is upgraded in memory only
+pub fn load_read_only() -> Result<Settings, String> {
+    let path = settings_path().ok_or("No config directory available")?;
+    if !path.exists() {
+        return Ok(Settings::default());
+    }
+    parse_file(&path).map(|(settings, _)| settings)
+}
+
+pub fn save(settings: &Settings) -> Result<(), String> {
+    settings.validate()?;
+    let path = settings_path().ok_or("No config directory available")?;
+    save_to(&path, settings)
+}
+
+pub type SettingsWatcher = Debouncer<notify_debouncer_mini::notify::RecommendedWatcher>;
+
+// Reloads the settings whenever the file changes. Valid settings are
+// published on `settings`; errors are passed to `on_error` and the
+// previous settings stay active. The returned watcher must be kept alive.
+pub fn watch(
+    settings: watch::Sender<Settings>,
+    on_error: impl Fn(String) + Send + 'static,
+) -> Result<SettingsWatcher, String> {
+    let path = settings_path().ok_or("No config directory available")?;
+    let dir = path
+        .parent()
+        .ok_or("Settings file has no parent directory")?
+        .to_path_buf();
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create config directory: {}", e))?;
+
+    let watched = path.clone();
+    // Text of our own migration write-back, which shouldn't count as a change
+    let mut written: Option<String> = None;
+    let mut debouncer = new_debouncer(
+        Duration::from_millis(500),
+        move |result: DebounceEventResult| {
+            let changed = match result {
+                Ok(events) => events
+                    .iter()
+                    .any(|e| e.path.file_name() == watched.file_name()),
+                Err(e) => {
+                    on_error(format!("Settings watcher error: {}", e));
+                    false
+                }
+            };
+            if !changed || !watched.exists() {
+                return;
+            }
+            if written.is_some() && fs::read_to_string(&watched).ok() == written.take() {
+                return;
+            }
+            match read_file(&watched) {
+                Ok((new_settings, upgraded)) => {
+                    written = upgraded;
+                    settings.send_if_modified(|current| {
+                        if *current == new_settings {
+                            false
+                        } else {
+                            *current = new_settings;
+                            true
+                        }
+                    });
+                }
+                Err(e) => on_error(e),
+            }
+        },
+    )
+    .map_err(|e| format!("Failed to watch settings: {}", e))?;
+
+    // Watch the directory since editors often replace the file on save
+    debouncer
+        .watcher()
+        .watch(&dir, RecursiveMode::NonRecursive)
+        .map_err(|e| format!("Failed to watch {}: {}", dir.display(), e))?;
+
+    Ok(debouncer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A file from before schema_version existed, with the fixed font sizes
+    // that version 2 dropped
+    const V1_FILE: &str = r#"
+[polling]
+interval_secs = 60
+
+[icon]
+style = "gauge"
+font_size_short = 14
+font_size_medium = 12
+font_size_long = 10
+"#;
+
+    #[test]
+    fn migrates_unversioned_file() {
+        let (settings, migrated) = Settings::parse(V1_FILE).unwrap();
+        assert!(migrated);
+        assert_eq!(settings.schema_version, SCHEMA_VERSION);
+        assert_eq!(settings.polling.interval_secs, 60);
+        assert_eq!(settings.icon.style, IconStyle::Gauge);
+        assert_eq!(settings.notifications, NotificationSettings::default());
+    }
+
+    #[test]
+    fn migration_drops_font_sizes() {
+        let mut table: toml::Table = V1_FILE.parse().unwrap();
+        drop_font_sizes(&mut table).unwrap();
+        let icon = table["icon"].as_table().unwrap();
+        assert_eq!(icon.keys().collect::<Vec<_>>(), vec!["style"]);
+        assert_eq!(table["polling"]["interval_secs"].as_integer(), Some(60));
+    }
+
+    #[test]
+    fn current_file_is_not_migrated() {
+        let content = Settings::default().to_toml().unwrap();
+        let (settings, migrated) = Settings::parse(&content).unwrap();
+        assert!(!migrated);
+        assert_eq!(settings, Settings::default());
+    }
+
+    #[test]
+    fn rejects_newer_schema_and_unknown_keys() {
+        let newer = format!("schema_version = {}", SCHEMA_VERSION + 1);
+        assert!(Settings::parse(&newer).unwrap_err().contains("newer"));
+        let removed = format!(
+            "schema_version = {}\n[icon]\nfont_size_short = 14",
+            SCHEMA_VERSION
+        );
+        assert!(Settings::parse(&removed).is_err());
+        assert!(Settings::parse("[polling]\ninterval_secs = 1").is_err());
+    }
+
+    #[test]
+    fn read_file_upgrades_in_place() {
+        let dir = std::env::temp_dir().join(format!("settings-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(SETTINGS_FILE);
+        fs::write(&path, V1_FILE).unwrap();
+
+        let (settings, upgraded) = read_file(&path).unwrap();
+        let on_disk = fs::read_to_string(&path).unwrap();
+        assert_eq!(upgraded.as_ref(), Some(&on_disk));
+        assert_eq!(Settings::parse(&on_disk).unwrap(), (settings, false));
+        assert_eq!(
+            fs::read_to_string(path.with_extension("toml.bak")).unwrap(),
+            V1_FILE
+        );
+
+        // Reading the upgraded file again writes nothing
+        assert_eq!(read_file(&path).unwrap().1, None);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
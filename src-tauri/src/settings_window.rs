@@ -0,0 +1,48 @@
+use crate::i18n;
+use crate::settings::{self, Settings, SCHEMA_VERSION};
+use crate::AppState;
+use tauri::{AppHandle, Manager, Runtime, State, WebviewUrl, WebviewWindowBuilder};
+
+const SETTINGS_WINDOW: &str = "settings";
+
+// Opens the settings window, or focuses it if it's already open
+pub fn open_settings_window<R: Runtime>(app: &AppHandle<R>) -> Result<(), tauri::Error> {
+    if let Some(window) = app.get_webview_window(SETTINGS_WINDOW) {
+        window.show()?;
+        return window.set_focus();
+    }
+
+    WebviewWindowBuilder::new(
+        app,
+        SETTINGS_WINDOW,
+        WebviewUrl::App("settings.html".into()),
+    )
+    .title(i18n::tr("settings-title"))
+    .inner_size(520.0, 680.0)
+    .resizable(true)
+    .build()?;
+
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_settings(state: State<'_, AppState>) -> Settings {
+    state.settings.borrow().clone()
+}
+
+#[tauri::command]
+pub fn validate_settings(settings: Settings) -> Result<(), String> {
+    settings.validate()
+}
+
+// Validates, writes the settings file and applies the settings right away
+#[tauri::command]
+pub fn save_settings(state: State<'_, AppState>, settings: Settings) -> Result<Settings, String> {
+    let settings = Settings {
+        schema_version: SCHEMA_VERSION,
+        ..settings
+    };
+    settings::save(&settings)?;
+    state.settings.send_replace(settings.clone());
+    Ok(settings)
+}
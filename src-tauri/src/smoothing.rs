@@ -0,0 +1,241 @@
+use crate::mouse_battery::{BatteryStatus, MouseModel};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+
+// Filter applied to raw percentage readings before they are presented
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FilterKind {
+    // Show raw readings as reported by the mouse
+    Off,
+    // Median of the last `window` readings
+    Median,
+    // Exponential moving average with factor `ema_alpha`
+    Ema,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FilterConfig {
+    pub kind: FilterKind,
+    pub window: usize,
+    pub ema_alpha: f32,
+    // Minimum change (in percent) before the shown level moves
+    pub hysteresis: u8,
+}
+
+impl Default for FilterConfig {
+    fn default() -> Self {
+        Self {
+            kind: FilterKind::Median,
+            window: 5,
+            ema_alpha: 0.3,
+            hysteresis: 2,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct FilterState {
+    samples: VecDeque<u8>,
+    ema: Option<f32>,
+    shown: Option<u8>,
+    charging: bool,
+}
+
+// Per-device smoothing of percentage readings. The shown level never goes
+// up while discharging and never goes down while charging.
+#[derive(Debug)]
+pub struct BatteryFilter {
+    config: FilterConfig,
+    states: HashMap<MouseModel, FilterState>,
+}
+
+impl BatteryFilter {
+    pub fn new(config: FilterConfig) -> Self {
+        Self {
+            config,
+            states: HashMap::new(),
+        }
+    }
+
+    pub fn set_config(&mut self, config: FilterConfig) {
+        if config != self.config {
+            self.config = config;
+            self.states.clear();
+        }
+    }
+
+    pub fn apply(&mut self, status: BatteryStatus) -> BatteryStatus {
+        if self.config.kind == FilterKind::Off {
+            return status;
+        }
+
+        let (percentage, charging, mouse_model) = match status {
+            BatteryStatus::Normal {
+                percentage,
+                mouse_model,
+            } => (percentage, false, mouse_model),
+            BatteryStatus::Charging {
+                percentage,
+                mouse_model,
+            } => (percentage, true, mouse_model),
+            _ => return status,
+        };
+
+        let config = self.config;
+        let state = self.states.entry(mouse_model).or_default();
+
+        // Switching between charging and discharging invalidates the history
+        if state.charging != charging {
+            *state = FilterState {
+                charging,
+                ..FilterState::default()
+            };
+        }
+
+        let candidate = match config.kind {
+            FilterKind::Median => {
+                state.samples.push_back(percentage);
+                while state.samples.len() > config.window.max(1) {
+                    state.samples.pop_front();
+                }
+                let mut sorted: Vec<u8> = state.samples.iter().copied().collect();
+                sorted.sort_unstable();
+                sorted[sorted.len() / 2]
+            }
+            FilterKind::Ema => {
+                let ema = match state.ema {
+                    Some(prev) => prev + config.ema_alpha * (percentage as f32 - prev),
+                    None => percentage as f32,
+                };
+                state.ema = Some(ema);
+                ema.round().clamp(1.0, 100.0) as u8
+            }
+            FilterKind::Off => percentage,
+        };
+
+        let shown = match state.shown {
+            None => candidate,
+            Some(shown) if candidate.abs_diff(shown) < config.hysteresis => shown,
+            Some(shown) if charging => candidate.max(shown),
+            Some(shown) => candidate.min(shown),
+        };
+        state.shown = Some(shown);
+
+        match status {
+            BatteryStatus::Charging { mouse_model, .. } => BatteryStatus::Charging {
+                percentage: shown,
+                mouse_model,
+            },
+            _ => BatteryStatus::Normal {
+                percentage: shown,
+                mouse_model,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn model() -> MouseModel {
+        MouseModel::from_product_id(0x2034)
+    }
+
+    fn normal(percentage: u8) -> BatteryStatus {
+        BatteryStatus::Normal {
+            percentage,
+            mouse_model: model(),
+        }
+    }
+
+    fn charging(percentage: u8) -> BatteryStatus {
+        BatteryStatus::Charging {
+            percentage,
+            mouse_model: model(),
+        }
+    }
+
+    fn shown(filter: &mut BatteryFilter, readings: &[BatteryStatus]) -> Vec<Option<u8>> {
+        readings
+            .iter()
+            .map(|status| filter.apply(status.clone()).percentage())
+            .collect()
+    }
+
+    #[test]
+    fn median_ignores_spikes() {
+        let mut filter = BatteryFilter::new(FilterConfig::default());
+        let readings = [normal(80), normal(80), normal(20), normal(80), normal(95)];
+        assert_eq!(shown(&mut filter, &readings), vec![Some(80); 5]);
+    }
+
+    #[test]
+    fn median_follows_monotonic_drain() {
+        let mut filter = BatteryFilter::new(FilterConfig::default());
+        let readings: Vec<_> = (70..=80).rev().map(normal).collect();
+        let levels: Vec<u8> = shown(&mut filter, &readings)
+            .into_iter()
+            .flatten()
+            .collect();
+
+        assert!(levels.windows(2).all(|pair| pair[1] <= pair[0]));
+        assert_eq!(levels, vec![80, 80, 80, 80, 78, 78, 76, 76, 74, 74, 72]);
+    }
+
+    #[test]
+    fn charging_transition_resets_history() {
+        let mut filter = BatteryFilter::new(FilterConfig::default());
+        let readings = [
+            normal(50),
+            normal(49),
+            charging(51),
+            charging(53),
+            charging(52),
+            normal(52),
+        ];
+        assert_eq!(
+            shown(&mut filter, &readings),
+            vec![Some(50), Some(50), Some(51), Some(53), Some(53), Some(52)]
+        );
+        assert!(matches!(
+            filter.apply(charging(54)),
+            BatteryStatus::Charging { .. }
+        ));
+    }
+
+    #[test]
+    fn ema_smooths_towards_reading() {
+        let mut filter = BatteryFilter::new(FilterConfig {
+            kind: FilterKind::Ema,
+            ema_alpha: 0.5,
+            hysteresis: 0,
+            ..FilterConfig::default()
+        });
+        let readings = [normal(80), normal(60), normal(60), normal(60)];
+        assert_eq!(
+            shown(&mut filter, &readings),
+            vec![Some(80), Some(70), Some(65), Some(63)]
+        );
+    }
+
+    #[test]
+    fn off_and_levelless_statuses_pass_through() {
+        let mut filter = BatteryFilter::new(FilterConfig {
+            kind: FilterKind::Off,
+            ..FilterConfig::default()
+        });
+        assert_eq!(
+            shown(&mut filter, &[normal(80), normal(20)]),
+            vec![Some(80), Some(20)]
+        );
+
+        let mut filter = BatteryFilter::new(FilterConfig::default());
+        let asleep = BatteryStatus::Asleep {
+            mouse_model: model(),
+        };
+        assert!(matches!(filter.apply(asleep), BatteryStatus::Asleep { .. }));
+    }
+}
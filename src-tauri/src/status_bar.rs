@@ -0,0 +1,124 @@
+use crate::icon;
+use crate::last_known::BatteryReading;
+use crate::mouse_battery::BatteryStatus;
+use crate::settings::Settings;
+use crate::{template, tooltip_text};
+use serde::Deserialize;
+use serde_json::json;
+
+// Text shown in the bar while a level is known, unless --text is given
+pub const DEFAULT_TEXT: &str = "{percent}%";
+
+// Output of `watch`, one line per change
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BarFormat {
+    // The text alone, for polybar, i3blocks and scripts
+    Plain,
+    // A JSON object per line for a waybar custom module with return-type json
+    Waybar,
+    // i3bar's JSON protocol, for i3bar and swaybar as status_command
+    I3bar,
+}
+
+// Everything a bar shows for the readings; the first one is shown as the
+// text, like the tray icon, and every device is listed in the tooltip
+pub struct BarStatus {
+    pub text: String,
+    pub tooltip: String,
+    pub classes: Vec<&'static str>,
+    pub percentage: Option<u8>,
+    // Level colour of the icon, e.g. #f5a623
+    pub color: Option<String>,
+}
+
+impl BarStatus {
+    pub fn new(readings: &[BatteryReading], settings: &Settings, text: &str) -> Self {
+        let reading = &readings[0];
+        let level = reading.level();
+        let text = match level {
+            Some(_) => template::render(text, reading),
+            // The icon's texts for asleep, waking, not found and unknown
+            None => icon::icon_text(reading, &settings.icon),
+        };
+        let tooltip: Vec<String> = readings
+            .iter()
+            .map(|reading| tooltip_text(reading, settings))
+            .collect();
+
+        Self {
+            text,
+            tooltip: tooltip.join("\n"),
+            classes: classes(reading, settings),
+            percentage: level,
+            color: level.map(|level| {
+                let [r, g, b, _] = icon::level_color(level, &settings.icon).0;
+                format!("#{:02x}{:02x}{:02x}", r, g, b)
+            }),
+        }
+    }
+
+    pub fn line(&self, format: BarFormat) -> String {
+        match format {
+            BarFormat::Plain => self.text.clone(),
+            BarFormat::Waybar => {
+                let mut object = json!({
+                    "text": self.text,
+                    "tooltip": self.tooltip,
+                    "class": self.classes,
+                });
+                if let Some(percentage) = self.percentage {
+                    object["percentage"] = percentage.into();
+                }
+                object.to_string()
+            }
+            // Each status is an element of an endless array
+            BarFormat::I3bar => {
+                let mut block = json!({
+                    "name": "mouse_battery",
+                    "full_text": self.text,
+                    "urgent": self.classes.contains(&"critical"),
+                });
+                if let Some(color) = &self.color {
+                    block["color"] = color.as_str().into();
+                }
+                format!("[{}],", block)
+            }
+        }
+    }
+}
+
+// Printed once before the first i3bar status
+pub const I3BAR_HEADER: &str = "{\"version\":1}\n[";
+
+// CSS classes for a reading: its state as in the icon pack manifest, plus
+// low or critical at the notification thresholds, stale while the level is
+// the last known one, and wired
+pub fn classes(reading: &BatteryReading, settings: &Settings) -> Vec<&'static str> {
+    let mut classes = vec![reading.status.state_name()];
+
+    let charging = matches!(
+        reading.status,
+        BatteryStatus::Charging { .. } | BatteryStatus::FullyCharged { .. }
+    );
+    if let (Some(level), false) = (reading.level(), charging) {
+        let notifications = &settings.notifications;
+        if level <= notifications.critical_threshold {
+            classes.push("critical");
+        }
+        if level <= notifications.low_threshold {
+            classes.push("low");
+        }
+    }
+    if reading.is_stale() {
+        classes.push("stale");
+    }
+    if reading
+        .status
+        .get_mouse_model()
+        .is_some_and(|mouse_model| mouse_model.is_wired())
+    {
+        classes.push("wired");
+    }
+    classes
+}
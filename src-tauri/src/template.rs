@@ -0,0 +1,136 @@
+use crate::estimate;
+use crate::i18n;
+use crate::last_known::BatteryReading;
+use crate::mouse_battery::BatteryStatus;
+
+// Placeholders available in the tooltip, menu and icon templates. Each is
+// described by the "placeholder-<name>" message.
+pub const PLACEHOLDERS: &[&str] = &[
+    "name",
+    "percent",
+    "state",
+    "firmware",
+    "age",
+    "remaining",
+    "connection",
+];
+
+pub fn describe(placeholder: &str) -> String {
+    i18n::tr(&format!("placeholder-{}", placeholder))
+}
+
+// Checks that a template only uses known placeholders and balanced braces
+pub fn validate(template: &str) -> Result<(), String> {
+    if template.trim().is_empty() {
+        return Err("must not be empty".to_string());
+    }
+    expand(template, |_| String::new()).map(|_| ())
+}
+
+// Whether a template refers to a placeholder, so costly values such as
+// the firmware version are only fetched when shown
+pub fn uses(template: &str, placeholder: &str) -> bool {
+    let mut found = false;
+    let _ = expand(template, |name| {
+        found |= name == placeholder;
+        String::new()
+    });
+    found
+}
+
+// Fills in a validated template; an invalid one is shown as written
+pub fn render(template: &str, reading: &BatteryReading) -> String {
+    expand(template, |name| value(name, reading)).unwrap_or_else(|_| template.to_string())
+}
+
+// Renders the template if one is configured, otherwise the built-in text
+pub fn render_or(template: Option<&str>, reading: &BatteryReading, default: String) -> String {
+    match template {
+        Some(template) => render(template, reading),
+        None => default,
+    }
+}
+
+fn value(name: &str, reading: &BatteryReading) -> String {
+    let mouse_model = reading.status.get_mouse_model();
+    match name {
+        "name" => reading.status.display_name(),
+        "percent" => reading
+            .level()
+            .map(|level| level.to_string())
+            .unwrap_or_else(|| "--".to_string()),
+        "state" => i18n::tr(match reading.status {
+            BatteryStatus::Normal { .. } => "value-discharging",
+            BatteryStatus::Charging { .. } => "value-charging",
+            BatteryStatus::FullyCharged { .. } => "value-full",
+            BatteryStatus::Asleep { .. } => "value-asleep",
+            BatteryStatus::WakingUp { .. } => "value-waking",
+            BatteryStatus::NotFound => "value-not-found",
+            BatteryStatus::Unknown { .. } => "value-unknown",
+        }),
+        "firmware" => reading
+            .firmware
+            .clone()
+            .unwrap_or_else(|| i18n::tr("value-unknown")),
+        "age" => reading
+            .last_known
+            .map(|last| last.format_age())
+            .unwrap_or_else(|| i18n::tr("age-just-now")),
+        "remaining" => reading
+            .remaining
+            .map(estimate::format_duration)
+            .unwrap_or_else(|| i18n::tr("value-unknown")),
+        "connection" => i18n::tr(match mouse_model {
+            Some(m) if m.is_wired() => "value-wired",
+            Some(_) => "value-wireless",
+            None => "value-unknown",
+        }),
+        _ => String::new(),
+    }
+}
+
+// Replaces each {placeholder} with `value(placeholder)`. Literal braces are
+// written {{ and }}.
+fn expand(template: &str, mut value: impl FnMut(&str) -> String) -> Result<String, String> {
+    let mut out = String::new();
+    let mut chars = template.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                out.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                out.push('}');
+            }
+            '{' => {
+                let mut name = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => name.push(c),
+                        None => return Err(format!("unclosed placeholder {{{}", name)),
+                    }
+                }
+                if !PLACEHOLDERS.contains(&name.as_str()) {
+                    let known: Vec<String> = PLACEHOLDERS
+                        .iter()
+                        .map(|known| format!("{{{}}}", known))
+                        .collect();
+                    return Err(format!(
+                        "unknown placeholder {{{}}} (expected one of {})",
+                        name,
+                        known.join(", ")
+                    ));
+                }
+                out.push_str(&value(&name));
+            }
+            '}' => return Err("unmatched }, write }} for a literal brace".to_string()),
+            c => out.push(c),
+        }
+    }
+
+    Ok(out)
+}
@@ -0,0 +1,160 @@
+use serde::{Deserialize, Serialize};
+use tokio::sync::watch;
+
+// Colour scheme of the panel the tray icon sits on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Theme {
+    Dark,
+    Light,
+}
+
+// `icon.theme` in the settings: follow the system or force a scheme
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ThemeSetting {
+    Auto,
+    Dark,
+    Light,
+}
+
+impl ThemeSetting {
+    // Theme to render with, given the one detected from the system
+    pub fn resolve(self, detected: Theme) -> Theme {
+        match self {
+            ThemeSetting::Auto => detected,
+            ThemeSetting::Dark => Theme::Dark,
+            ThemeSetting::Light => Theme::Light,
+        }
+    }
+}
+
+// Keeps `theme` in sync with the system colour scheme until the detection
+// fails. Panels are assumed dark until then, matching the original white icons.
+pub async fn watch(theme: watch::Sender<Theme>) {
+    if let Err(e) = watch_system_theme(&theme).await {
+        eprintln!("Theme detection unavailable: {}", e);
+    }
+}
+
+// The XDG desktop portal reports the scheme and signals when it changes
+#[cfg(target_os = "linux")]
+async fn watch_system_theme(theme: &watch::Sender<Theme>) -> Result<(), String> {
+    use futures_util::StreamExt;
+
+    let connection = zbus::Connection::session()
+        .await
+        .map_err(|e| e.to_string())?;
+    let portal = portal::SettingsProxy::new(&connection)
+        .await
+        .map_err(|e| e.to_string())?;
+    let mut changes = portal
+        .receive_setting_changed()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let value = portal
+        .read_one(portal::APPEARANCE, portal::COLOR_SCHEME)
+        .await
+        .map_err(|e| e.to_string())?;
+    if let Some(detected) = portal::theme_from_value(&value) {
+        theme.send_replace(detected);
+    }
+
+    while let Some(signal) = changes.next().await {
+        let Ok(args) = signal.args() else {
+            continue;
+        };
+        if args.namespace == portal::APPEARANCE && args.key == portal::COLOR_SCHEME {
+            if let Some(detected) = portal::theme_from_value(&args.value) {
+                theme.send_if_modified(|current| std::mem::replace(current, detected) != detected);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+mod portal {
+    use super::Theme;
+    use zbus::zvariant::{OwnedValue, Value};
+
+    pub const APPEARANCE: &str = "org.freedesktop.appearance";
+    pub const COLOR_SCHEME: &str = "color-scheme";
+
+    #[zbus::proxy(
+        interface = "org.freedesktop.portal.Settings",
+        default_service = "org.freedesktop.portal.Desktop",
+        default_path = "/org/freedesktop/portal/desktop",
+        gen_blocking = false
+    )]
+    pub trait Settings {
+        fn read_one(&self, namespace: &str, key: &str) -> zbus::Result<OwnedValue>;
+
+        #[zbus(signal)]
+        fn setting_changed(&self, namespace: &str, key: &str, value: Value<'_>)
+            -> zbus::Result<()>;
+    }
+
+    // color-scheme is 1 for dark, 2 for light and 0 for no preference
+    pub fn theme_from_value(value: &Value<'_>) -> Option<Theme> {
+        match value {
+            Value::U32(1) => Some(Theme::Dark),
+            Value::U32(2) => Some(Theme::Light),
+            Value::U32(_) => None,
+            Value::Value(inner) => theme_from_value(inner),
+            _ => None,
+        }
+    }
+}
+
+// Windows and macOS have no change notification we can subscribe to
+// without a window, so poll the setting instead
+#[cfg(not(target_os = "linux"))]
+async fn watch_system_theme(theme: &watch::Sender<Theme>) -> Result<(), String> {
+    let mut interval = tokio::time::interval(std::time::Duration::from_secs(10));
+    loop {
+        interval.tick().await;
+        let detected = tokio::task::spawn_blocking(detect_system_theme)
+            .await
+            .map_err(|e| e.to_string())?;
+        if let Some(detected) = detected {
+            theme.send_if_modified(|current| std::mem::replace(current, detected) != detected);
+        }
+    }
+}
+
+// The taskbar follows the system theme, not the apps theme
+#[cfg(windows)]
+fn detect_system_theme() -> Option<Theme> {
+    use winreg::enums::HKEY_CURRENT_USER;
+    use winreg::RegKey;
+
+    let personalize = RegKey::predef(HKEY_CURRENT_USER)
+        .open_subkey(r"Software\Microsoft\Windows\CurrentVersion\Themes\Personalize")
+        .ok()?;
+    let light: u32 = personalize.get_value("SystemUsesLightTheme").ok()?;
+    match light {
+        0 => Some(Theme::Dark),
+        _ => Some(Theme::Light),
+    }
+}
+
+// AppleInterfaceStyle is only set in dark mode
+#[cfg(target_os = "macos")]
+fn detect_system_theme() -> Option<Theme> {
+    let output = std::process::Command::new("defaults")
+        .args(["read", "-g", "AppleInterfaceStyle"])
+        .output()
+        .ok()?;
+    if String::from_utf8_lossy(&output.stdout).trim() == "Dark" {
+        Some(Theme::Dark)
+    } else {
+        Some(Theme::Light)
+    }
+}
+
+#[cfg(not(any(windows, target_os = "linux", target_os = "macos")))]
+fn detect_system_theme() -> Option<Theme> {
+    None
+}
@@ -0,0 +1,597 @@
+use crate::i18n;
+use crate::last_known::BatteryReading;
+use crate::mouse_battery::BatteryStatus;
+use crate::settings::Settings;
+use crate::template;
+use std::collections::HashMap;
+use std::time::Duration;
+use tauri::menu::{
+    CheckMenuItemBuilder, IsMenuItem, Menu, MenuItemBuilder, MenuItemKind, PredefinedMenuItem,
+    Submenu,
+};
+use tauri::{Manager, Runtime};
+
+// One entry of the tray menu. The menu is described as data first and
+// turned into native items by `LiveMenu`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MenuEntry {
+    Item {
+        id: String,
+        text: String,
+        enabled: bool,
+    },
+    Check {
+        id: String,
+        text: String,
+        checked: bool,
+    },
+    Submenu {
+        id: String,
+        text: String,
+        entries: Vec<MenuEntry>,
+    },
+    Separator,
+}
+
+impl MenuEntry {
+    fn item(id: &str, text: String) -> Self {
+        MenuEntry::Item {
+            id: id.to_string(),
+            text,
+            enabled: true,
+        }
+    }
+
+    // Display-only line
+    fn label(id: String, text: String) -> Self {
+        MenuEntry::Item {
+            id,
+            text,
+            enabled: false,
+        }
+    }
+}
+
+// Everything the menu shows besides the settings
+pub struct MenuState<'a> {
+    // Latest reading per device, the one shown in the icon first
+    pub readings: &'a [BatteryReading],
+    pub autostart_enabled: bool,
+    // Time left while monitoring is paused
+    pub paused: Option<Duration>,
+}
+
+pub fn model(state: &MenuState, settings: &Settings) -> Vec<MenuEntry> {
+    let menu_settings = &settings.menu;
+    let mut entries = Vec::new();
+
+    // Status of the device shown in the icon, display only
+    if let (true, Some(primary)) = (menu_settings.show_status, state.readings.first()) {
+        let text = template::render_or(
+            menu_settings.status_text.as_deref(),
+            primary,
+            primary.get_tooltip(),
+        );
+        entries.push(MenuEntry::label("status".to_string(), text));
+    }
+
+    if menu_settings.show_devices {
+        entries.extend(
+            state
+                .readings
+                .iter()
+                .filter(|reading| !matches!(reading.status, BatteryStatus::NotFound))
+                .map(device_submenu),
+        );
+    }
+    separate(&mut entries);
+
+    if menu_settings.show_refresh {
+        entries.push(MenuEntry::item("refresh", i18n::tr("menu-refresh")));
+    }
+    if menu_settings.show_quick_actions {
+        let pause_text = match state.paused {
+            Some(left) => i18n::tr_with(
+                "menu-paused",
+                &[("minutes", left.as_secs().div_ceil(60).into())],
+            ),
+            None => i18n::tr("menu-pause"),
+        };
+        entries.push(MenuEntry::Check {
+            id: "pause".to_string(),
+            text: pause_text,
+            checked: state.paused.is_some(),
+        });
+        entries.push(MenuEntry::item("copy_status", i18n::tr("menu-copy-status")));
+        entries.push(MenuEntry::item("open_logs", i18n::tr("menu-open-logs")));
+        entries.push(MenuEntry::item(
+            "open_history",
+            i18n::tr("menu-open-history"),
+        ));
+    }
+    if menu_settings.show_firmware {
+        entries.push(MenuEntry::item("firmware", i18n::tr("menu-firmware")));
+    }
+    separate(&mut entries);
+
+    if menu_settings.show_autostart {
+        entries.push(MenuEntry::Check {
+            id: "autostart".to_string(),
+            text: i18n::tr("menu-autostart"),
+            checked: state.autostart_enabled,
+        });
+    }
+    entries.push(MenuEntry::item("settings", i18n::tr("menu-settings")));
+    entries.push(MenuEntry::item("quit", i18n::tr("menu-exit")));
+
+    entries
+}
+
+// Starts a new group, without leading or doubled separators
+fn separate(entries: &mut Vec<MenuEntry>) {
+    if !matches!(entries.last(), None | Some(MenuEntry::Separator)) {
+        entries.push(MenuEntry::Separator);
+    }
+}
+
+// Id prefix of a device's submenu, e.g. "device:2034"
+pub fn device_id(reading: &BatteryReading) -> String {
+    let product_id = reading
+        .status
+        .get_mouse_model()
+        .and_then(|m| m.product_id())
+        .unwrap_or(0);
+    format!("device:{:04x}", product_id)
+}
+
+fn device_submenu(reading: &BatteryReading) -> MenuEntry {
+    let id = device_id(reading);
+    let mouse_model = reading.status.get_mouse_model();
+
+    let level = match reading.level() {
+        Some(percent) => i18n::tr_with("device-level", &[("percent", percent.into())]),
+        None => i18n::tr("device-level-unknown"),
+    };
+    let state = i18n::tr(match reading.status {
+        BatteryStatus::Normal { .. } => "state-discharging",
+        BatteryStatus::Charging { .. } => "state-charging",
+        BatteryStatus::FullyCharged { .. } => "state-full",
+        BatteryStatus::Asleep { .. } => "state-asleep",
+        BatteryStatus::WakingUp { .. } => "state-waking",
+        BatteryStatus::NotFound | BatteryStatus::Unknown { .. } => "state-unknown",
+    });
+    let firmware = match &reading.firmware {
+        Some(version) => i18n::tr_with("device-firmware", &[("version", version.clone().into())]),
+        None => i18n::tr("device-firmware-unknown"),
+    };
+    let connection = if mouse_model.is_some_and(|m| m.is_wired()) {
+        i18n::tr("device-wired")
+    } else {
+        i18n::tr("device-wireless")
+    };
+    let age = match reading.last_known {
+        Some(last) => last.format_age(),
+        None => i18n::tr("age-just-now"),
+    };
+
+    MenuEntry::Submenu {
+        text: reading.get_tooltip(),
+        entries: vec![
+            MenuEntry::label(format!("{}:level", id), level),
+            MenuEntry::label(
+                format!("{}:state", id),
+                i18n::tr_with("device-state", &[("state", state.into())]),
+            ),
+            MenuEntry::label(format!("{}:firmware", id), firmware),
+            MenuEntry::label(format!("{}:connection", id), connection),
+            MenuEntry::label(
+                format!("{}:updated", id),
+                i18n::tr_with("device-updated", &[("age", age.into())]),
+            ),
+        ],
+        id,
+    }
+}
+
+// The tray's native menu together with the model it shows. Updates change
+// the text and state of existing items; the menu is only rebuilt when its
+// layout changes, e.g. when a mouse is plugged in or removed.
+pub struct LiveMenu<R: Runtime> {
+    menu: Menu<R>,
+    entries: Vec<MenuEntry>,
+    // Native items by id, including those inside submenus
+    items: HashMap<String, MenuItemKind<R>>,
+}
+
+impl<R: Runtime> LiveMenu<R> {
+    pub fn new<M: Manager<R>>(manager: &M, entries: Vec<MenuEntry>) -> tauri::Result<Self> {
+        let mut items = HashMap::new();
+        let top = build_items(manager, &entries, &mut items)?;
+        let menu = Menu::with_items(manager, &as_dyn(&top))?;
+        Ok(Self {
+            menu,
+            entries,
+            items,
+        })
+    }
+
+    pub fn menu(&self) -> &Menu<R> {
+        &self.menu
+    }
+
+    // Shows `entries`, returning true when a new menu had to be built and
+    // needs to be handed to the tray
+    pub fn update<M: Manager<R>>(
+        &mut self,
+        manager: &M,
+        entries: Vec<MenuEntry>,
+    ) -> tauri::Result<bool> {
+        if !same_layout(&self.entries, &entries) {
+            *self = Self::new(manager, entries)?;
+            return Ok(true);
+        }
+
+        self.update_items(&self.entries, &entries)?;
+        self.entries = entries;
+        Ok(false)
+    }
+
+    fn update_items(&self, old: &[MenuEntry], new: &[MenuEntry]) -> tauri::Result<()> {
+        for pair in old.iter().zip(new) {
+            match pair {
+                (
+                    MenuEntry::Item {
+                        id,
+                        text: old_text,
+                        enabled: old_enabled,
+                    },
+                    MenuEntry::Item { text, enabled, .. },
+                ) => {
+                    let Some(item) = self.items.get(id).and_then(|i| i.as_menuitem()) else {
+                        continue;
+                    };
+                    if old_text != text {
+                        item.set_text(text)?;
+                    }
+                    if old_enabled != enabled {
+                        item.set_enabled(*enabled)?;
+                    }
+                }
+                (
+                    MenuEntry::Check {
+                        id, text: old_text, ..
+                    },
+                    MenuEntry::Check { text, checked, .. },
+                ) => {
+                    let Some(item) = self.items.get(id).and_then(|i| i.as_check_menuitem()) else {
+                        continue;
+                    };
+                    if old_text != text {
+                        item.set_text(text)?;
+                    }
+                    // Set even when unchanged: the native item toggles itself
+                    // on click, also when the action behind it failed
+                    item.set_checked(*checked)?;
+                }
+                (
+                    MenuEntry::Submenu {
+                        id,
+                        text: old_text,
+                        entries: old_entries,
+                    },
+                    MenuEntry::Submenu { text, entries, .. },
+                ) => {
+                    if old_text != text {
+                        if let Some(submenu) = self.items.get(id).and_then(|i| i.as_submenu()) {
+                            submenu.set_text(text)?;
+                        }
+                    }
+                    self.update_items(old_entries, entries)?;
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+// Same kinds of entries with the same ids in the same order, whatever their
+// text or state
+fn same_layout(old: &[MenuEntry], new: &[MenuEntry]) -> bool {
+    old.len() == new.len()
+        && old.iter().zip(new).all(|pair| match pair {
+            (MenuEntry::Item { id: a, .. }, MenuEntry::Item { id: b, .. })
+            | (MenuEntry::Check { id: a, .. }, MenuEntry::Check { id: b, .. }) => a == b,
+            (
+                MenuEntry::Submenu {
+                    id: a,
+                    entries: a_entries,
+                    ..
+                },
+                MenuEntry::Submenu {
+                    id: b,
+                    entries: b_entries,
+                    ..
+                },
+            ) => a == b && same_layout(a_entries, b_entries),
+            (MenuEntry::Separator, MenuEntry::Separator) => true,
+            _ => false,
+        })
+}
+
+// Creates the native items for a menu model, recording each one by id
+fn build_items<R: Runtime, M: Manager<R>>(
+    manager: &M,
+    entries: &[MenuEntry],
+    items: &mut HashMap<String, MenuItemKind<R>>,
+) -> tauri::Result<Vec<MenuItemKind<R>>> {
+    let mut built = Vec::new();
+    for entry in entries {
+        let (id, item) = match entry {
+            MenuEntry::Item { id, text, enabled } => (
+                id,
+                MenuItemKind::MenuItem(
+                    MenuItemBuilder::with_id(id, text)
+                        .enabled(*enabled)
+                        .build(manager)?,
+                ),
+            ),
+            MenuEntry::Check { id, text, checked } => (
+                id,
+                MenuItemKind::Check(
+                    CheckMenuItemBuilder::with_id(id, text)
+                        .checked(*checked)
+                        .build(manager)?,
+                ),
+            ),
+            MenuEntry::Submenu { id, text, entries } => {
+                let children = build_items(manager, entries, items)?;
+                (
+                    id,
+                    MenuItemKind::Submenu(Submenu::with_id_and_items(
+                        manager,
+                        id,
+                        text,
+                        true,
+                        &as_dyn(&children),
+                    )?),
+                )
+            }
+            MenuEntry::Separator => {
+                built.push(MenuItemKind::Predefined(PredefinedMenuItem::separator(
+                    manager,
+                )?));
+                continue;
+            }
+        };
+        items.insert(id.clone(), item.clone());
+        built.push(item);
+    }
+    Ok(built)
+}
+
+fn as_dyn<R: Runtime>(items: &[MenuItemKind<R>]) -> Vec<&dyn IsMenuItem<R>> {
+    items
+        .iter()
+        .map(|item| item as &dyn IsMenuItem<R>)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::last_known::LastKnown;
+    use crate::mouse_battery::MouseModel;
+    use std::time::SystemTime;
+
+    fn reading(status: BatteryStatus) -> BatteryReading {
+        BatteryReading {
+            status,
+            last_known: None,
+            remaining: None,
+            firmware: None,
+            raw_percentage: None,
+        }
+    }
+
+    fn discharging(product_id: u16, percentage: u8) -> BatteryReading {
+        reading(BatteryStatus::Normal {
+            percentage,
+            mouse_model: MouseModel::from_product_id(product_id),
+        })
+    }
+
+    fn state(readings: &[BatteryReading]) -> MenuState<'_> {
+        MenuState {
+            readings,
+            autostart_enabled: true,
+            paused: None,
+        }
+    }
+
+    // Ids in order, "-" for separators
+    fn ids(entries: &[MenuEntry]) -> Vec<&str> {
+        entries
+            .iter()
+            .map(|entry| match entry {
+                MenuEntry::Item { id, .. }
+                | MenuEntry::Check { id, .. }
+                | MenuEntry::Submenu { id, .. } => id.as_str(),
+                MenuEntry::Separator => "-",
+            })
+            .collect()
+    }
+
+    #[test]
+    fn shows_entries_the_settings_turn_on() {
+        let readings = [discharging(0x2034, 60), reading(BatteryStatus::NotFound)];
+        let mut settings = Settings::default();
+        assert_eq!(
+            ids(&model(&state(&readings), &settings)),
+            [
+                "status",
+                "device:2034",
+                "-",
+                "refresh",
+                "pause",
+                "copy_status",
+                "open_logs",
+                "open_history",
+                "firmware",
+                "-",
+                "autostart",
+                "settings",
+                "quit",
+            ]
+        );
+
+        settings.menu.show_status = false;
+        settings.menu.show_devices = false;
+        settings.menu.show_quick_actions = false;
+        assert_eq!(
+            ids(&model(&state(&readings), &settings)),
+            ["refresh", "firmware", "-", "autostart", "settings", "quit"]
+        );
+
+        // Settings and Exit stay, without a separator before them
+        settings.menu.show_refresh = false;
+        settings.menu.show_firmware = false;
+        settings.menu.show_autostart = false;
+        assert_eq!(
+            ids(&model(&state(&readings), &settings)),
+            ["settings", "quit"]
+        );
+    }
+
+    #[test]
+    fn status_line_and_checks_follow_the_state() {
+        let readings = [discharging(0x2034, 60)];
+        let mut settings = Settings::default();
+        settings.menu.status_text = Some("{percent}%".to_string());
+        let menu_state = MenuState {
+            readings: &readings,
+            autostart_enabled: false,
+            paused: Some(Duration::from_secs(90)),
+        };
+        let entries = model(&menu_state, &settings);
+
+        assert_eq!(
+            entries[0],
+            MenuEntry::label("status".to_string(), "60%".to_string())
+        );
+        let check = |id: &str| {
+            entries.iter().find_map(|entry| match entry {
+                MenuEntry::Check {
+                    id: check_id,
+                    text,
+                    checked,
+                } if check_id == id => Some((text.clone(), *checked)),
+                _ => None,
+            })
+        };
+        assert_eq!(
+            check("pause"),
+            Some((i18n::tr_with("menu-paused", &[("minutes", 2.into())]), true))
+        );
+        assert_eq!(check("autostart").map(|(_, checked)| checked), Some(false));
+    }
+
+    #[test]
+    fn lists_each_mouse_in_a_submenu() {
+        let mut asleep = reading(BatteryStatus::Asleep {
+            mouse_model: MouseModel::from_product_id(0x2011),
+        });
+        asleep.last_known = Some(LastKnown {
+            percentage: 35,
+            charging: false,
+            recorded_at: SystemTime::now(),
+        });
+        let mut wired = discharging(0x2034, 80);
+        wired.firmware = Some("1.2.3.4".to_string());
+        let readings = [wired, asleep];
+        let entries = model(&state(&readings), &Settings::default());
+
+        let submenus: Vec<_> = entries
+            .iter()
+            .filter_map(|entry| match entry {
+                MenuEntry::Submenu { id, entries, .. } => Some((id.as_str(), entries)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(submenus.len(), 2);
+
+        let (id, lines) = submenus[0];
+        assert_eq!(id, "device:2034");
+        assert_eq!(
+            ids(lines),
+            [
+                "device:2034:level",
+                "device:2034:state",
+                "device:2034:firmware",
+                "device:2034:connection",
+                "device:2034:updated",
+            ]
+        );
+        assert_eq!(
+            lines[0],
+            MenuEntry::label(
+                "device:2034:level".to_string(),
+                i18n::tr_with("device-level", &[("percent", 80.into())])
+            )
+        );
+        assert_eq!(
+            lines[2],
+            MenuEntry::label(
+                "device:2034:firmware".to_string(),
+                i18n::tr_with("device-firmware", &[("version", "1.2.3.4".into())])
+            )
+        );
+
+        // An asleep mouse shows its last known level
+        let (id, lines) = submenus[1];
+        assert_eq!(id, "device:2011");
+        assert_eq!(
+            lines[0],
+            MenuEntry::label(
+                "device:2011:level".to_string(),
+                i18n::tr_with("device-level", &[("percent", 35.into())])
+            )
+        );
+        assert_eq!(
+            lines[1],
+            MenuEntry::label(
+                "device:2011:state".to_string(),
+                i18n::tr_with(
+                    "device-state",
+                    &[("state", i18n::tr("state-asleep").into())]
+                )
+            )
+        );
+    }
+
+    #[test]
+    fn layout_changes_with_the_set_of_mice() {
+        let settings = Settings::default();
+        let one = [discharging(0x2034, 60)];
+        let before = model(&state(&one), &settings);
+
+        // New levels only change text
+        let lower = [discharging(0x2034, 55)];
+        let after = model(&state(&lower), &settings);
+        assert_ne!(before, after);
+        assert!(same_layout(&before, &after));
+
+        // A mouse plugged in, removed or swapped for another needs a rebuild
+        let two = [discharging(0x2034, 60), discharging(0x2011, 30)];
+        let plugged_in = model(&state(&two), &settings);
+        assert!(!same_layout(&before, &plugged_in));
+        assert!(!same_layout(&plugged_in, &before));
+        let other = [discharging(0x2011, 60)];
+        assert!(!same_layout(&before, &model(&state(&other), &settings)));
+
+        // So does a toggle in the settings
+        let mut fewer = settings.clone();
+        fewer.menu.show_quick_actions = false;
+        assert!(!same_layout(&before, &model(&state(&one), &fewer)));
+    }
+}
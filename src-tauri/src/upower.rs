@@ -0,0 +1,217 @@
+use crate::dbus_service::DeviceProperties;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use zbus::object_server::SignalEmitter;
+
+// Values from UPower's up-types.h
+const TYPE_MOUSE: u32 = 5;
+const STATE_UNKNOWN: u32 = 0;
+const STATE_CHARGING: u32 = 1;
+const STATE_DISCHARGING: u32 = 2;
+const STATE_FULLY_CHARGED: u32 = 4;
+const TECHNOLOGY_UNKNOWN: u32 = 0;
+const WARNING_LEVEL_NONE: u32 = 1;
+const BATTERY_LEVEL_NONE: u32 = 1;
+
+// A mouse as org.freedesktop.UPower.Device, next to our own interface on
+// the same object, for battery applets that read UPower devices
+pub struct UPowerDevice {
+    props: DeviceProperties,
+    native_path: String,
+    // Seconds since the epoch of the last change
+    update_time: u64,
+}
+
+impl UPowerDevice {
+    pub fn new(native_path: String, props: DeviceProperties) -> Self {
+        Self {
+            props,
+            native_path,
+            update_time: now(),
+        }
+    }
+
+    // Takes the new properties and sends PropertiesChanged for the UPower
+    // ones that differ
+    pub async fn update(
+        &mut self,
+        props: DeviceProperties,
+        emitter: &SignalEmitter<'_>,
+    ) -> zbus::Result<()> {
+        let old = std::mem::replace(&mut self.props, props);
+        self.update_time = now();
+        self.update_time_changed(emitter).await?;
+
+        if old.percentage != self.props.percentage {
+            self.percentage_changed(emitter).await?;
+        }
+        if old.state != self.props.state {
+            self.state_changed(emitter).await?;
+        }
+        if old.state != self.props.state || old.percentage != self.props.percentage {
+            self.icon_name_changed(emitter).await?;
+        }
+        if old.model != self.props.model {
+            self.model_changed(emitter).await?;
+        }
+        if old.remaining != self.props.remaining || old.state != self.props.state {
+            self.time_to_empty_changed(emitter).await?;
+            self.time_to_full_changed(emitter).await?;
+        }
+        Ok(())
+    }
+
+    fn upower_state(&self) -> u32 {
+        match self.props.state.as_str() {
+            "charging" => STATE_CHARGING,
+            "discharging" => STATE_DISCHARGING,
+            "full" => STATE_FULLY_CHARGED,
+            // Asleep and waking mice keep their last known percentage
+            _ => STATE_UNKNOWN,
+        }
+    }
+
+    fn remaining_secs(&self, state: u32) -> i64 {
+        match self.props.remaining {
+            Some(remaining) if self.upower_state() == state => remaining.as_secs() as i64,
+            _ => 0,
+        }
+    }
+}
+
+// Read-only; every property UPower documents that a mouse has
+#[zbus::interface(name = "org.freedesktop.UPower.Device")]
+impl UPowerDevice {
+    // The level is read on every poll, so there's nothing to do
+    fn refresh(&self) {}
+
+    fn get_history(&self, _kind: &str, _timespan: u32, _resolution: u32) -> Vec<(u32, f64, u32)> {
+        Vec::new()
+    }
+
+    fn get_statistics(&self, _kind: &str) -> Vec<(f64, f64)> {
+        Vec::new()
+    }
+
+    #[zbus(property)]
+    fn native_path(&self) -> &str {
+        &self.native_path
+    }
+
+    #[zbus(property)]
+    fn vendor(&self) -> &str {
+        "Glorious"
+    }
+
+    #[zbus(property)]
+    fn model(&self) -> &str {
+        &self.props.model
+    }
+
+    #[zbus(property)]
+    fn serial(&self) -> &str {
+        ""
+    }
+
+    #[zbus(property)]
+    fn update_time(&self) -> u64 {
+        self.update_time
+    }
+
+    #[zbus(property, name = "Type")]
+    fn device_type(&self) -> u32 {
+        TYPE_MOUSE
+    }
+
+    // Not powering the computer, so never counted in the system's battery
+    #[zbus(property)]
+    fn power_supply(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn has_history(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn has_statistics(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn online(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn is_present(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn is_rechargeable(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn percentage(&self) -> f64 {
+        f64::from(self.props.percentage.max(0))
+    }
+
+    #[zbus(property)]
+    fn state(&self) -> u32 {
+        self.upower_state()
+    }
+
+    #[zbus(property)]
+    fn time_to_empty(&self) -> i64 {
+        self.remaining_secs(STATE_DISCHARGING)
+    }
+
+    #[zbus(property)]
+    fn time_to_full(&self) -> i64 {
+        self.remaining_secs(STATE_CHARGING)
+    }
+
+    #[zbus(property)]
+    fn technology(&self) -> u32 {
+        TECHNOLOGY_UNKNOWN
+    }
+
+    // Low battery is notified by the app itself, see [notifications]
+    #[zbus(property)]
+    fn warning_level(&self) -> u32 {
+        WARNING_LEVEL_NONE
+    }
+
+    // The mouse reports a percentage, not coarse levels
+    #[zbus(property)]
+    fn battery_level(&self) -> u32 {
+        BATTERY_LEVEL_NONE
+    }
+
+    #[zbus(property)]
+    fn icon_name(&self) -> String {
+        icon_name(self.upower_state(), self.props.percentage)
+    }
+}
+
+// Symbolic icon as UPower names them, e.g. battery-level-40-charging-symbolic
+fn icon_name(state: u32, percentage: i32) -> String {
+    if percentage < 0 {
+        return "battery-missing-symbolic".to_string();
+    }
+    let level = percentage.min(100) / 10 * 10;
+    match state {
+        STATE_FULLY_CHARGED => "battery-level-100-charged-symbolic".to_string(),
+        STATE_CHARGING => format!("battery-level-{}-charging-symbolic", level),
+        _ => format!("battery-level-{}-symbolic", level),
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or(Duration::ZERO)
+        .as_secs()
+}